@@ -0,0 +1,666 @@
+//! Compact, versioned binary format for a `Table`.
+//!
+//! A table is written as a header, an index and its values in id order. All
+//! integers are little-endian:
+//!
+//! - the 8-byte magic number `SYMBLMAP`;
+//! - the format version, as a `u32`;
+//! - a single byte identifying the `SymbolId` type (see [IdWidth](enum.IdWidth.html));
+//! - the number of symbols, as a `u64`;
+//! - for each symbol, the position in the file of its encoded value as a `u64`,
+//!   followed by the position of the end of the last value;
+//! - the number of ids sorted by value, as a `u64`, followed by those ids, each
+//!   as a `u64`. This is the number of symbols if the values hold their
+//!   encoded form (see `Codec::encoded()`), as strings do, and 0 otherwise;
+//! - for each symbol, the length of its encoded value as a `u64`, followed by
+//!   the encoded value itself.
+//!
+//! The index lets a table be looked up in place, as by
+//! [MmapTable](../mmap/struct.MmapTable.html). Version 1 of the format, which
+//! has no index, can still be read. Ids are only written in the index sorted
+//! by value, and the ids of the values themselves are implied by their order,
+//! since they must form a dense range starting at 0.
+//! Values are encoded with the [Codec](trait.Codec.html) trait, which is
+//! implemented for `String`, `Vec<u8>` and Rust's integer types.
+//!
+//! # Example
+//!
+//! ```
+//! use symbol_map::Table;
+//!
+//! let mut table = Table::<String, u16>::new();
+//! table.insert("NNP".to_string());
+//! table.insert("VBD".to_string());
+//!
+//! let mut bytes = Vec::new();
+//! table.write_to(&mut bytes).unwrap();
+//! let table = Table::<String, u16>::read_from(&bytes[..]).unwrap();
+//! assert_eq!(table.get(&1).unwrap().data(), "VBD");
+//! ```
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use super::{SymbolId, Table};
+
+/// Magic number at the start of every binary table.
+pub const MAGIC: &[u8; 8] = b"SYMBLMAP";
+
+/// Version of the binary format written by `Table::write_to()`.
+pub const VERSION: u32 = 2;
+
+/// Identifies the `SymbolId` type of a binary table, so that a table is not
+/// read back with ids of a different width than it was written with.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IdWidth {
+    /// `u8` ids.
+    U8,
+    /// `u16` ids.
+    U16,
+    /// `u32` ids.
+    U32,
+    /// `u64` ids.
+    U64,
+    /// `usize` ids.
+    Usize,
+}
+
+impl IdWidth {
+    /// Returns the byte used to identify this width in a table header.
+    pub fn tag(&self) -> u8 {
+        match *self {
+            IdWidth::U8 => 1,
+            IdWidth::U16 => 2,
+            IdWidth::U32 => 4,
+            IdWidth::U64 => 8,
+            IdWidth::Usize => 0,
+        }
+    }
+
+    /// Returns the width identified by `tag` in a table header, if any.
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(IdWidth::U8),
+            2 => Some(IdWidth::U16),
+            4 => Some(IdWidth::U32),
+            8 => Some(IdWidth::U64),
+            0 => Some(IdWidth::Usize),
+            _ => None,
+        }
+    }
+}
+
+/// A `SymbolId` that may be used in a binary table.
+pub trait BinaryId: SymbolId {
+    /// The width recorded in the header of tables with this id type.
+    const WIDTH: IdWidth;
+
+    /// Converts an id written as a `u64`, or returns `None` if it does not fit.
+    fn from_u64(id: u64) -> Option<Self>;
+}
+
+macro_rules! binary_id {
+    ($($t:ty => $width:ident),*) => {
+        $(
+            impl BinaryId for $t {
+                const WIDTH: IdWidth = IdWidth::$width;
+
+                fn from_u64(id: u64) -> Option<Self> {
+                    <$t>::try_from(id).ok()
+                }
+            }
+        )*
+    }
+}
+
+binary_id!(u8 => U8, u16 => U16, u32 => U32, u64 => U64, usize => Usize);
+
+/// Conversion of table values to and from bytes.
+pub trait Codec: Sized {
+    /// Appends the encoded form of `self` to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a value from `bytes`, which hold exactly one encoded value.
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError>;
+
+    /// Returns the encoded form of `self` if `self` holds it as is, as a
+    /// `String` holds its UTF-8 bytes, so that it need not be copied. Tables
+    /// of such values are written with an index sorted by value. Either every
+    /// value of a type returns `Some`, or none does.
+    fn encoded(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn encoded(&self) -> Option<&[u8]> {
+        Some(self.as_bytes())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| DecodeError::new(e.to_string()))
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn encoded(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+macro_rules! integer_codec {
+    ($($t:ty),*) => {
+        $(
+            impl Codec for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+                    let mut le_bytes = [0u8; ::std::mem::size_of::<$t>()];
+                    if bytes.len() != le_bytes.len() {
+                        return Err(DecodeError::new(format!(
+                            "expected {} bytes for {}, found {}",
+                            le_bytes.len(), stringify!($t), bytes.len())))
+                    }
+                    le_bytes.copy_from_slice(bytes);
+                    Ok(<$t>::from_le_bytes(le_bytes))
+                }
+            }
+        )*
+    }
+}
+
+integer_codec!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// `usize` values are written as `u64`s, so that tables are portable between
+/// platforms.
+impl Codec for usize {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as u64).encode(buf)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let value = u64::decode(bytes)?;
+        if value > usize::MAX as u64 {
+            return Err(DecodeError::new(format!("{} does not fit in a usize", value)))
+        }
+        Ok(value as usize)
+    }
+}
+
+/// `isize` values are written as `i64`s, so that tables are portable between
+/// platforms.
+impl Codec for isize {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as i64).encode(buf)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let value = i64::decode(bytes)?;
+        if value > isize::MAX as i64 || value < isize::MIN as i64 {
+            return Err(DecodeError::new(format!("{} does not fit in an isize", value)))
+        }
+        Ok(value as isize)
+    }
+}
+
+/// Error produced by `Codec::decode()` when bytes do not hold a valid value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    message: String,
+}
+
+impl DecodeError {
+    /// Creates an error with a description of what was wrong with the input.
+    pub fn new<S>(message: S) -> Self where S: Into<String> {
+        DecodeError { message: message.into(), }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for DecodeError { }
+
+/// Error produced when reading a binary table.
+#[derive(Debug)]
+pub enum BinaryError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The input ended before the table was complete.
+    Truncated,
+    /// The input does not start with the expected magic number.
+    BadMagic,
+    /// The input was written with an unsupported format version.
+    UnsupportedVersion(u32),
+    /// The input was written with a different `SymbolId` type than the one
+    /// being read. Holds the tag found in the header.
+    IdWidthMismatch {
+        /// Width of the ids being read.
+        expected: IdWidth,
+        /// Tag found in the header.
+        found: u8,
+    },
+    /// The input holds more symbols than the `SymbolId` type can identify.
+    TooManySymbols(u64),
+    /// The value of the symbol with the given id could not be decoded.
+    InvalidValue {
+        /// Index of the symbol in id order.
+        index: u64,
+        /// Reason the value could not be decoded.
+        error: DecodeError,
+    },
+    /// The index of the table holds a position outside its values, or does not
+    /// list every symbol in order of their values.
+    CorruptIndex,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BinaryError::Io(ref e) => write!(f, "I/O error reading symbol table: {}", e),
+            BinaryError::Truncated => f.write_str("symbol table is truncated"),
+            BinaryError::BadMagic => f.write_str("input is not a symbol table"),
+            BinaryError::UnsupportedVersion(v) =>
+                write!(f, "unsupported symbol table format version {}", v),
+            BinaryError::IdWidthMismatch { expected, found } =>
+                match IdWidth::from_tag(found) {
+                    Some(width) => write!(f, "symbol table has {:?} ids, expected {:?}",
+                                          width, expected),
+                    None => write!(f, "symbol table has unknown id width tag {}", found),
+                },
+            BinaryError::TooManySymbols(n) =>
+                write!(f, "symbol table has {} symbols, more than its ids can identify", n),
+            BinaryError::InvalidValue { index, ref error } =>
+                write!(f, "invalid value for symbol {}: {}", index, error),
+            BinaryError::CorruptIndex => f.write_str("symbol table index is corrupt"),
+        }
+    }
+}
+
+impl Error for BinaryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            BinaryError::Io(ref e) => Some(e),
+            BinaryError::InvalidValue { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BinaryError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            BinaryError::Truncated
+        } else {
+            BinaryError::Io(e)
+        }
+    }
+}
+
+/// Length in bytes of the header of a binary table.
+pub(crate) const HEADER_LEN: usize = 21;
+
+/// Reads and validates the header of a binary table with ids of type `D` from
+/// `reader`. Returns the format version and the number of symbols in the table.
+pub(crate) fn read_header<R, D>(reader: &mut R) -> Result<(u32, u64), BinaryError>
+    where R: Read, D: BinaryId {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(BinaryError::BadMagic)
+    }
+    let version = read_u32(reader)?;
+    if version != 1 && version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version))
+    }
+    let mut tag = [0u8; 1];
+    reader.read_exact(&mut tag)?;
+    if tag[0] != D::WIDTH.tag() {
+        return Err(BinaryError::IdWidthMismatch { expected: D::WIDTH, found: tag[0], })
+    }
+    let len = read_u64(reader)?;
+    if len > 0 && len - 1 > D::max_value().as_usize() as u64 {
+        return Err(BinaryError::TooManySymbols(len))
+    }
+    Ok((version, len))
+}
+
+/// Returns the encoded form of `value`, borrowed from `value` if possible or
+/// else encoded into `buf`.
+fn encoded<'a, T>(value: &'a T, buf: &'a mut Vec<u8>) -> &'a [u8] where T: Codec {
+    match value.encoded() {
+        Some(bytes) => bytes,
+        None => {
+            buf.clear();
+            value.encode(buf);
+            buf
+        },
+    }
+}
+
+/// Reads and discards `len` bytes from `reader`.
+fn skip<R>(reader: &mut R, len: u64) -> Result<(), BinaryError> where R: Read {
+    if io::copy(&mut reader.take(len), &mut io::sink())? < len {
+        return Err(BinaryError::Truncated)
+    }
+    Ok(())
+}
+
+/// Reads a little-endian `u32` from `reader`.
+fn read_u32<R>(reader: &mut R) -> Result<u32, BinaryError> where R: Read {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a little-endian `u64` from `reader`.
+fn read_u64<R>(reader: &mut R) -> Result<u64, BinaryError> where R: Read {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+impl<T, D> Table<T, D> where T: Codec, D: BinaryId {
+    /// Writes the table to `writer` in the binary format described in the
+    /// [binary](binary/index.html) module.
+    ///
+    /// Returns an error of kind `InvalidInput`, without writing anything, if
+    /// the ids in the table do not form a dense range starting at 0, since
+    /// they could not be restored when the table is read.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()> where W: Write {
+        if !self.is_dense() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "symbol ids do not form a dense range starting at 0"))
+        }
+        // Only the ids and borrowed encodings of the values are sorted, and
+        // values that would have to be encoded to be compared are not indexed.
+        let mut by_value: Vec<(&[u8], usize)> = self.iter().rev().enumerate()
+            .map_while(|(id, symbol)| symbol.data().encoded().map(|bytes| (bytes, id)))
+            .collect();
+        if by_value.len() < self.len() {
+            by_value = Vec::new();
+        }
+        by_value.sort_unstable();
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[D::WIDTH.tag()])?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        // The positions of the values are computed from their lengths, so that
+        // the values can be written afterwards as they are encoded.
+        let mut position = (HEADER_LEN + 8 * (self.len() + 2 + by_value.len())) as u64;
+        let mut buf = Vec::new();
+        for symbol in self.iter().rev() {
+            position += 8;
+            writer.write_all(&position.to_le_bytes())?;
+            position += encoded(symbol.data(), &mut buf).len() as u64;
+        }
+        writer.write_all(&position.to_le_bytes())?;
+        writer.write_all(&(by_value.len() as u64).to_le_bytes())?;
+        for &(_, id) in by_value.iter() {
+            writer.write_all(&(id as u64).to_le_bytes())?;
+        }
+        for symbol in self.iter().rev() {
+            let bytes = encoded(symbol.data(), &mut buf);
+            writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a table in the binary format described in the
+    /// [binary](binary/index.html) module from `reader`. Each value receives
+    /// the id it had when the table was written.
+    ///
+    /// Returns an error if the input is not a binary table, was written with a
+    /// different id type, or is corrupt or truncated.
+    pub fn read_from<R>(mut reader: R) -> Result<Self, BinaryError> where R: Read {
+        let (version, len) = read_header::<_, D>(&mut reader)?;
+        if version > 1 {
+            // The index is only needed for lookups in place, so it is skipped.
+            skip(&mut reader, len.saturating_add(1).saturating_mul(8))?;
+            let sorted_len = read_u64(&mut reader)?;
+            skip(&mut reader, sorted_len.saturating_mul(8))?;
+        }
+        let mut table = Table::new();
+        let mut buf = Vec::new();
+        for index in 0..len {
+            let value_len = read_u64(&mut reader)?;
+            buf.clear();
+            // Read through take() rather than allocating value_len bytes up
+            // front, so that a corrupt length cannot exhaust memory.
+            (&mut reader).take(value_len).read_to_end(&mut buf)?;
+            if (buf.len() as u64) < value_len {
+                return Err(BinaryError::Truncated)
+            }
+            let value = T::decode(&buf)
+                .map_err(|error| BinaryError::InvalidValue { index, error, })?;
+            if table.try_insert(value).is_err() {
+                return Err(BinaryError::TooManySymbols(len))
+            }
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BinaryError, Codec, IdWidth, MAGIC, VERSION};
+    use ::Table;
+
+    const VALUES: &[&str] = &["NNP", "VBD", "", "DT", "NN"];
+
+    fn string_table() -> Table<String, u16> {
+        let mut t = Table::new();
+        for v in VALUES.iter() {
+            t.insert(v.to_string());
+        }
+        t
+    }
+
+    fn to_bytes<T, D>(t: &Table<T, D>) -> Vec<u8> where T: Codec, D: super::BinaryId {
+        let mut bytes = Vec::new();
+        t.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Reads `bytes` as little-endian `u64`s.
+    fn u64s(bytes: &[u8]) -> Vec<u64> {
+        bytes.chunks(8)
+            .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect()
+    }
+
+    #[test]
+    fn header_ok() {
+        let bytes = to_bytes(&string_table());
+        assert_eq!(&bytes[0..8], MAGIC);
+        assert_eq!(&bytes[8..12], &VERSION.to_le_bytes());
+        assert_eq!(bytes[12], IdWidth::U16.tag());
+        assert_eq!(&bytes[13..21], &(VALUES.len() as u64).to_le_bytes());
+
+        // Positions of the values, which start after the 6 positions, the
+        // count and 5 ids of the index and an 8-byte length each.
+        assert_eq!(u64s(&bytes[21..69]), vec![125, 136, 147, 155, 165, bytes.len() as u64]);
+        assert_eq!(u64s(&bytes[69..77]), vec![5]);
+        assert_eq!(u64s(&bytes[77..117]), vec![2, 3, 4, 0, 1]);
+        assert_eq!(&bytes[117..125], &3u64.to_le_bytes());
+        assert_eq!(&bytes[125..128], b"NNP");
+    }
+
+    #[test]
+    fn unsorted_header_ok() {
+        // Integers don't hold their encoded form, so they are not sorted.
+        let mut t = Table::<u32, u8>::new();
+        t.insert_all(vec![7, 3]);
+        let bytes = to_bytes(&t);
+        assert_eq!(u64s(&bytes[21..53]), vec![61, 73, 77, 0]);
+        assert_eq!(bytes.len(), 77);
+        let t = Table::<u32, u8>::read_from(&bytes[..]).unwrap();
+        assert_eq!(t.iter().rev().map(|s| *s.data()).collect::<Vec<_>>(), vec![7, 3]);
+    }
+
+    #[test]
+    fn version_1_ok() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(IdWidth::U16.tag());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        for v in ["NNP", "VBD"].iter() {
+            bytes.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(v.as_bytes());
+        }
+        let t = Table::<String, u16>::read_from(&bytes[..]).unwrap();
+        assert_eq!(t.len(), 2);
+        assert_eq!(t.get(&1).unwrap().data(), "VBD");
+    }
+
+    #[test]
+    fn string_round_trip_ok() {
+        let bytes = to_bytes(&string_table());
+        let t = Table::<String, u16>::read_from(&bytes[..]).unwrap();
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            assert_eq!(t.get(&(i as u16)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn sparse_ids_err() {
+        let mut t = string_table();
+        t.remap(|symbol| Some(*symbol.id() * 2));
+        let mut bytes = Vec::new();
+        let e = t.write_to(&mut bytes).unwrap_err();
+        assert_eq!(e.kind(), ::std::io::ErrorKind::InvalidInput);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn empty_round_trip_ok() {
+        let bytes = to_bytes(&Table::<Vec<u8>, usize>::new());
+        let t = Table::<Vec<u8>, usize>::read_from(&bytes[..]).unwrap();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn integer_round_trip_ok() {
+        let mut t = Table::<i64, u32>::new();
+        for v in [0, -1, i64::MAX, i64::MIN, 42].iter() {
+            t.insert(*v);
+        }
+        let bytes = to_bytes(&t);
+        let t2 = Table::<i64, u32>::read_from(&bytes[..]).unwrap();
+        assert_eq!(t2.iter().map(|s| *s.data()).collect::<Vec<i64>>(),
+                   t.iter().map(|s| *s.data()).collect::<Vec<i64>>());
+
+        let mut t = Table::<usize, u8>::new();
+        for v in 0..256 {
+            t.insert(v * 1000);
+        }
+        let bytes = to_bytes(&t);
+        let t = Table::<usize, u8>::read_from(&bytes[..]).unwrap();
+        assert_eq!(t.len(), 256);
+        assert_eq!(*t.get(&255).unwrap().data(), 255000);
+    }
+
+    #[test]
+    fn bad_magic_err() {
+        let mut bytes = to_bytes(&string_table());
+        bytes[0] = b'X';
+        match Table::<String, u16>::read_from(&bytes[..]) {
+            Err(BinaryError::BadMagic) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn unsupported_version_err() {
+        let mut bytes = to_bytes(&string_table());
+        bytes[8..12].copy_from_slice(&7u32.to_le_bytes());
+        match Table::<String, u16>::read_from(&bytes[..]) {
+            Err(BinaryError::UnsupportedVersion(7)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn id_width_mismatch_err() {
+        let bytes = to_bytes(&string_table());
+        match Table::<String, u32>::read_from(&bytes[..]) {
+            Err(BinaryError::IdWidthMismatch { expected: IdWidth::U32, found: 2 }) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn too_many_symbols_err() {
+        let mut bytes = to_bytes(&Table::<String, u8>::new());
+        bytes[13..21].copy_from_slice(&257u64.to_le_bytes());
+        match Table::<String, u8>::read_from(&bytes[..]) {
+            Err(BinaryError::TooManySymbols(257)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn truncated_err() {
+        let bytes = to_bytes(&string_table());
+        for len in 0..bytes.len() {
+            match Table::<String, u16>::read_from(&bytes[..len]) {
+                Err(BinaryError::Truncated) => (),
+                x => panic!("length {}: {:?}", len, x),
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_length_err() {
+        let mut bytes = to_bytes(&string_table());
+        bytes[117..125].copy_from_slice(&u64::MAX.to_le_bytes());
+        match Table::<String, u16>::read_from(&bytes[..]) {
+            Err(BinaryError::Truncated) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn invalid_value_err() {
+        let mut bytes = to_bytes(&string_table());
+        bytes[125] = 0xff;
+        match Table::<String, u16>::read_from(&bytes[..]) {
+            Err(BinaryError::InvalidValue { index: 0, .. }) => (),
+            x => panic!("{:?}", x),
+        }
+
+        let mut t = Table::<u32, u8>::new();
+        t.insert(1);
+        let mut bytes = to_bytes(&t);
+        // Claim that the value is one byte shorter than a u32.
+        bytes[45..53].copy_from_slice(&3u64.to_le_bytes());
+        bytes.pop();
+        match Table::<u32, u8>::read_from(&bytes[..]) {
+            Err(BinaryError::InvalidValue { index: 0, .. }) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+}
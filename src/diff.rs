@@ -0,0 +1,227 @@
+//! Comparison of two vocabularies.
+//!
+//! [diff()](fn.diff.html) compares the symbols of two indexings over the same
+//! type of data, such as two versions of a vocabulary, and reports which values
+//! were added or removed and which kept or changed their ids. A
+//! [DiffSummary](struct.DiffSummary.html) counts these changes and can be
+//! printed.
+//!
+//! # Example
+//!
+//! ```
+//! use symbol_map::diff::diff;
+//! use symbol_map::indexing::{HashIndexing, Indexing};
+//!
+//! let mut old = HashIndexing::<&str, usize>::default();
+//! old.encode_or_insert(vec!["the", "cat", "sat"]);
+//! let mut new = HashIndexing::<&str, usize>::default();
+//! new.encode_or_insert(vec!["the", "sat", "dog"]);
+//!
+//! let d = diff(&old, &new);
+//! assert_eq!(d.added().map(|s| *s.data()).collect::<Vec<_>>(), vec!["dog"]);
+//! assert_eq!(d.removed().map(|s| *s.data()).collect::<Vec<_>>(), vec!["cat"]);
+//! assert_eq!(d.moved().map(|(a, b)| (*a.id(), *b.id())).collect::<Vec<_>>(), vec![(2, 1)]);
+//! assert_eq!(d.summary().to_string(), "1 added, 1 removed, 1 unchanged, 1 moved");
+//! ```
+
+use std::fmt;
+use std::hash::Hash;
+
+use indexing::Indexing;
+use ::{Symbol, SymbolId};
+
+/// A symbol of the first indexing and the symbol of the second indexing that
+/// has the same value.
+type Pair<'a, T, A, B> = (&'a Symbol<T, A>, &'a Symbol<T, B>);
+
+/// The differences between the symbols of two indexings, as computed by
+/// `diff()`.
+///
+/// Symbols of the first indexing are visited in id order, as are added
+/// symbols of the second.
+#[derive(Debug)]
+pub struct Diff<'a, T, A, B> where T: 'a, A: 'a + SymbolId, B: 'a + SymbolId {
+    added: Vec<&'a Symbol<T, B>>,
+    removed: Vec<&'a Symbol<T, A>>,
+    same: Vec<Pair<'a, T, A, B>>,
+    moved: Vec<Pair<'a, T, A, B>>,
+}
+
+impl<'a, T, A, B> Diff<'a, T, A, B> where T: 'a, A: 'a + SymbolId, B: 'a + SymbolId {
+    /// Returns the symbols of the second indexing whose values are not in the
+    /// first.
+    pub fn added<'s>(&'s self) -> impl Iterator<Item = &'a Symbol<T, B>> + 's {
+        self.added.iter().cloned()
+    }
+
+    /// Returns the symbols of the first indexing whose values are not in the
+    /// second.
+    pub fn removed<'s>(&'s self) -> impl Iterator<Item = &'a Symbol<T, A>> + 's {
+        self.removed.iter().cloned()
+    }
+
+    /// Returns pairs of symbols from the first and second indexing that have
+    /// the same value and the same id.
+    pub fn same<'s>(&'s self)
+                    -> impl Iterator<Item = (&'a Symbol<T, A>, &'a Symbol<T, B>)> + 's {
+        self.same.iter().cloned()
+    }
+
+    /// Returns pairs of symbols from the first and second indexing that have
+    /// the same value but different ids.
+    pub fn moved<'s>(&'s self)
+                     -> impl Iterator<Item = (&'a Symbol<T, A>, &'a Symbol<T, B>)> + 's {
+        self.moved.iter().cloned()
+    }
+
+    /// Returns `true` iff the two indexings hold the same values with the same
+    /// ids.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+
+    /// Counts the differences.
+    pub fn summary(&self) -> DiffSummary {
+        DiffSummary {
+            added: self.added.len(),
+            removed: self.removed.len(),
+            same: self.same.len(),
+            moved: self.moved.len(),
+        }
+    }
+}
+
+/// Counts of the differences between two indexings, which can be printed with
+/// `Display`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DiffSummary {
+    added: usize,
+    removed: usize,
+    same: usize,
+    moved: usize,
+}
+
+impl DiffSummary {
+    /// Returns the number of values only in the second indexing.
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Returns the number of values only in the first indexing.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Returns the number of values that have the same id in both indexings.
+    pub fn same(&self) -> usize {
+        self.same
+    }
+
+    /// Returns the number of values that have different ids in the two
+    /// indexings.
+    pub fn moved(&self) -> usize {
+        self.moved
+    }
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} added, {} removed, {} unchanged, {} moved",
+               self.added, self.removed, self.same, self.moved)
+    }
+}
+
+/// Compares the symbols of `a` and `b`, which may use different types of ids.
+/// Ids are compared by their `usize` values. Symbols that are in a table but
+/// not found by looking up their values, such as the tombstones inserted by
+/// `HashIndexing::align_to()`, are ignored.
+pub fn diff<'a, T, A, B>(a: &'a A, b: &'a B) -> Diff<'a, T, A::Id, B::Id>
+    where T: Eq + Hash, A: Indexing<Data = T>, B: Indexing<Data = T> {
+    let mut diff = Diff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        same: Vec::new(),
+        moved: Vec::new(),
+    };
+    for old in a.table().iter().rev().filter(|&old| is_indexed(a, old)) {
+        match b.get(old.data()) {
+            Some(new) if old.id().as_usize() == new.id().as_usize() => diff.same.push((old, new)),
+            Some(new) => diff.moved.push((old, new)),
+            None => diff.removed.push(old),
+        }
+    }
+    for new in b.table().iter().rev().filter(|&new| is_indexed(b, new)) {
+        if a.get(new.data()).is_none() {
+            diff.added.push(new);
+        }
+    }
+    diff
+}
+
+/// Returns `true` iff looking up the value of `symbol` in `index` finds it.
+fn is_indexed<T, I>(index: &I, symbol: &Symbol<T, I::Id>) -> bool
+    where T: Eq + Hash, I: Indexing<Data = T> {
+    index.get(symbol.data()).map(|found| found.id()) == Some(symbol.id())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, DiffSummary};
+    use indexing::{AlignPolicy, HashIndexing, Indexing};
+    use ::Symbol;
+
+    #[test]
+    fn diff_ok() {
+        let mut a = HashIndexing::<String, usize>::default();
+        a.encode_or_insert("a b c d e".split(' ').map(String::from));
+        let mut b = HashIndexing::<String, u16>::default();
+        b.encode_or_insert("a c b f e g".split(' ').map(String::from));
+        let d = diff(&a, &b);
+        assert_eq!(d.added().map(|s| (s.data().as_str(), *s.id())).collect::<Vec<_>>(),
+                   vec![("f", 3), ("g", 5)]);
+        assert_eq!(d.removed().map(|s| (s.data().as_str(), *s.id())).collect::<Vec<_>>(),
+                   vec![("d", 3)]);
+        fn pairs<'a>((x, y): (&'a Symbol<String, usize>, &'a Symbol<String, u16>))
+                     -> (&'a str, usize, u16) {
+            (x.data().as_str(), *x.id(), *y.id())
+        }
+        assert_eq!(d.same().map(pairs).collect::<Vec<_>>(), vec![("a", 0, 0), ("e", 4, 4)]);
+        assert_eq!(d.moved().map(pairs).collect::<Vec<_>>(), vec![("b", 1, 2), ("c", 2, 1)]);
+        assert!(!d.is_empty());
+
+        let summary = d.summary();
+        assert_eq!((summary.added(), summary.removed(), summary.same(), summary.moved()),
+                   (2, 1, 2, 2));
+        assert_eq!(summary.to_string(), "2 added, 1 removed, 2 unchanged, 2 moved");
+    }
+
+    #[test]
+    fn diff_identical_ok() {
+        let mut a = HashIndexing::<usize, usize>::default();
+        a.encode_or_insert(0..10);
+        let d = diff(&a, &a);
+        assert!(d.is_empty());
+        assert_eq!(d.same().count(), 10);
+        assert_eq!(d.summary(), DiffSummary { same: 10, ..DiffSummary::default() });
+
+        let empty = HashIndexing::<usize, usize>::default();
+        let d = diff(&empty, &a);
+        assert_eq!(d.added().count(), 10);
+        assert_eq!(d.summary().to_string(), "10 added, 0 removed, 0 unchanged, 0 moved");
+        assert!(diff(&empty, &empty).is_empty());
+    }
+
+    #[test]
+    fn diff_ignores_tombstones_ok() {
+        let mut a = HashIndexing::<&str, usize>::default();
+        a.encode_or_insert(vec!["a", "b", "c"]);
+        let mut b = HashIndexing::<&str, usize>::default();
+        b.encode_or_insert(vec!["c", "a"]);
+        b.align_to(&a, AlignPolicy::Tombstone("_"));
+        let d = diff(&a, &b);
+        assert_eq!(d.added().count(), 0);
+        assert_eq!(d.removed().map(|s| *s.data()).collect::<Vec<_>>(), vec!["b"]);
+        assert_eq!(d.summary().to_string(), "0 added, 1 removed, 2 unchanged, 0 moved");
+        assert_eq!(diff(&b, &a).summary().to_string(), "1 added, 0 removed, 2 unchanged, 0 moved");
+    }
+}
@@ -3,22 +3,41 @@
 //! It is anticipated that most uses cases will be covered by
 //! [HashIndexing](struct.HashIndexing.html), which owns a
 //! [Table](../struct.Table.html) and provides bidirectional mappings between
-//! data values and their symbols.
+//! data values and their symbols. For building vocabularies from a corpus,
+//! [CountingIndexing](struct.CountingIndexing.html) additionally tracks how
+//! often each symbol occurs, and [FrozenIndexing](struct.FrozenIndexing.html)
+//! wraps a finished vocabulary to map unknown values to a fallback symbol. A
+//! `HashIndexing` that will only be read from can be converted to a compact,
+//! immutable [FrozenHashIndexing](struct.FrozenHashIndexing.html).
+//! [ConcurrentHashIndexing](struct.ConcurrentHashIndexing.html) allows many
+//! threads to insert symbols into one shared index, while
+//! [ShardedIndexing](struct.ShardedIndexing.html) gives each thread its own
+//! index and merges them afterwards.
 //!
 //! The [Indexing](trait.Indexing.html) trait is provided in case another lookup
 //! method is needed.
 
-use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::cmp::{self, Eq, Ord, Ordering, PartialEq, PartialOrd};
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
 use std::default::Default;
+use std::error::Error;
 use std::fmt;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter::{Extend, FromIterator};
+use std::mem;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
-use super::{Symbol, SymbolId, Table};
+use hashbrown::{Equivalent, HashMap};
+use hashbrown::hash_map::{RawEntryMut, RawVacantEntryMut};
+
+use super::{CapacityError, Extension, RemapError, Symbol, SymbolId, Table};
 
 /// Indicates whether the result of a symbol lookup had to create a new table
 /// entry.
-#[derive(Clone, Eq, Ord, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Ord, Hash, PartialEq, PartialOrd)]
 pub enum Insertion<T>  {
     /// Result came from an item that was already present in table.
     Present(T),
@@ -69,27 +88,79 @@ impl<T> Insertion<T> {
     }
 }
 
+/// Error returned by `Indexing::encode()` when a value has no symbol in the
+/// index.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownSymbol {
+    position: usize,
+}
+
+impl UnknownSymbol {
+    /// Returns the position in the input sequence of the first value that has
+    /// no symbol.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for UnknownSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no symbol for value at position {}", self.position)
+    }
+}
+
+impl Error for UnknownSymbol { }
+
+/// Error returned by `Indexing::decode()` when an id has no symbol in the
+/// index.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownId<D> where D: SymbolId {
+    position: usize,
+    id: D,
+}
+
+impl<D> UnknownId<D> where D: SymbolId {
+    /// Returns the position in the input sequence of the first id that has no
+    /// symbol.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the id that has no symbol.
+    pub fn id(&self) -> &D {
+        &self.id
+    }
+}
+
+impl<D> fmt::Display for UnknownId<D> where D: SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no symbol for id {:?} at position {}", self.id, self.position)
+    }
+}
+
+impl<D> Error for UnknownId<D> where D: SymbolId { }
+
 /// Wrapper for a raw pointer which lets us treat it like a reference.
 ///
 /// You are strongly discouraged from exposing this type directly in your data
 /// structures. This type is essentially a giant footgun. In particular:
 ///
 /// - No safety checks or lifetimes protect this reference, so a `Ref<T>` may be
-/// invalidated without warning. (You may use a `Ref<T>` safely by ensuring that
-/// the references passed to `Ref<T>::new()` will never be dropped before the
-/// wrappers. A good example of when you'd be able to do this is in in a struct
-/// that has `Ref<T>` references into a data structure that it also owns.)
+///   invalidated without warning. (You may use a `Ref<T>` safely by ensuring that
+///   the references passed to `Ref<T>::new()` will never be dropped before the
+///   wrappers. A good example of when you'd be able to do this is in in a struct
+///   that has `Ref<T>` references into a data structure that it also owns.)
 ///
 /// - The impls for `Debug`, `Eq`, `Hash`, `Ord`, `PartialEq`, and `PartialOrd`
-/// all dereference the raw pointer that this structure wraps. As a result, a
-/// `Ref<T>` must be removed from any data structures that make use of any of
-/// those interfaces *before* it is invalidated.
+///   all dereference the raw pointer that this structure wraps. As a result, a
+///   `Ref<T>` must be removed from any data structures that make use of any of
+///   those interfaces *before* it is invalidated.
 ///
 /// - `Ref<T>` wraps a value of type `*const T`, which is not usually `Send` or
-/// `Sync`. This restriction is overridden for a `Ref<T>` wrapper so that data
-/// structures which encapsulate it may themselves be `Send` or `Sync`. This
-/// makes it the responsibility of data structures using such wrappers to
-/// satisfy the contracts of those types.
+///   `Sync`. This restriction is overridden for a `Ref<T>` wrapper so that data
+///   structures which encapsulate it may themselves be `Send` or `Sync`. This
+///   makes it the responsibility of data structures using such wrappers to
+///   satisfy the contracts of those types.
 pub struct Ref<T> { ptr: *const T, }
 
 unsafe impl<T> Send for Ref<T> where T: Send { }
@@ -114,7 +185,7 @@ impl<T> Ref<T> {
 
 impl<T> Clone for Ref<T> {
     fn clone(&self) -> Self {
-        Ref { ptr: self.ptr, }
+        *self
     }
 }
 
@@ -158,6 +229,22 @@ impl<T> PartialOrd for Ref<T> where T: PartialOrd {
     }
 }
 
+/// A borrowed form of a `T` that can be used to look up a `Ref<T>` in a
+/// `HashMap`, for types `Q` such that `T: Borrow<Q>`.
+struct Query<'a, Q>(&'a Q) where Q: 'a + ?Sized;
+
+impl<'a, Q> Hash for Query<'a, Q> where Q: 'a + ?Sized + Hash {
+    fn hash<H>(&self, h: &mut H) where H: Hasher {
+        self.0.hash(h)
+    }
+}
+
+impl<'a, T, Q> Equivalent<Ref<T>> for Query<'a, Q> where T: Borrow<Q>, Q: 'a + ?Sized + Eq {
+    fn equivalent(&self, key: &Ref<T>) -> bool {
+        unsafe { key.deref() }.borrow() == self.0
+    }
+}
+
 /// Provides indexing for a `Table`, so that its elements may be retrieved
 /// efficiently. Most table lookups should go through an implementation of this
 /// trait structure instead of a `Table` directly.
@@ -188,240 +275,2804 @@ pub trait Indexing: Default {
 
     /// Looks up `data` in the index. Returns `Some(&symbol)` if a symbol is
     /// present, else `None`.
-    fn get(&self, data: &Self::Data) -> Option<&Symbol<Self::Data, Self::Id>>;
+    ///
+    /// As with `HashMap::get()`, `data` may be any borrowed form of
+    /// `Self::Data`, so that (for example) a `&str` may be looked up in an index
+    /// of `String`s without allocating.
+    fn get<Q>(&self, data: &Q) -> Option<&Symbol<Self::Data, Self::Id>>
+        where Self::Data: Borrow<Q>, Q: ?Sized + Eq + Hash;
+
+    /// Looks up `data` in the index, as `get()` does. Returns `Some(id)` with
+    /// the id of its symbol if a symbol is present, else `None`.
+    fn get_symbol_id<Q>(&self, data: &Q) -> Option<Self::Id>
+        where Self::Data: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get(data).map(|symbol| *symbol.id())
+    }
 
     /// Looks up `data` in the index, inserting it into the index and `table` if
     /// it isn't present. Returns the resulting `&Symbol<T>` wrapped in an
     /// `Insertion` that indicates whether a new table entry had to be created.
-    fn get_or_insert<'s>(&'s mut self, data: Self::Data)
-                         -> Insertion<&'s Symbol<Self::Data, Self::Id>>;
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `Self::Id` has
+    /// already been assigned. Use `try_get_or_insert()` to handle this case.
+    fn get_or_insert(&mut self, data: Self::Data)
+                     -> Insertion<&Symbol<Self::Data, Self::Id>>;
+
+    /// Looks up `data` in the index, inserting it as `get_or_insert()` does. If
+    /// a new table entry is needed but every value of `Self::Id` has already
+    /// been assigned, returns an error holding `data` instead.
+    #[allow(clippy::type_complexity)]
+    fn try_get_or_insert(&mut self, data: Self::Data)
+                         -> Result<Insertion<&Symbol<Self::Data, Self::Id>>,
+                                   CapacityError<Self::Data>>;
 
     /// Looks up the symbol with id `i` in the index. Returns `Some(symbol)` if
     /// a symbol is present, else `None`.
     fn get_symbol<'s>(&'s self, id: &Self::Id) -> Option<&'s Symbol<Self::Data, Self::Id>>;
+
+    /// Looks up each value in `data`, as `get()` does, and returns the ids of
+    /// their symbols in order.
+    ///
+    /// Returns an error holding the position of the first value that has no
+    /// symbol, if any.
+    fn encode<'q, I, Q>(&self, data: I) -> Result<Vec<Self::Id>, UnknownSymbol>
+        where I: IntoIterator<Item = &'q Q>, Self::Data: Borrow<Q>, Q: 'q + ?Sized + Eq + Hash {
+        data.into_iter().enumerate()
+            .map(|(position, value)| self.get_symbol_id(value).ok_or(UnknownSymbol { position, }))
+            .collect()
+    }
+
+    /// Looks up each value in `data`, inserting the ones that aren't present as
+    /// `get_or_insert()` does, and returns the ids of their symbols in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `Self::Id` has
+    /// already been assigned, as `get_or_insert()` does.
+    fn encode_or_insert<I>(&mut self, data: I) -> Vec<Self::Id>
+        where I: IntoIterator<Item = Self::Data> {
+        data.into_iter().map(|value| *self.get_or_insert(value).unwrap().id()).collect()
+    }
+
+    /// Looks up the symbol for each id in `ids`, as `get_symbol()` does, and
+    /// returns their values in order.
+    ///
+    /// Returns an error holding the position of the first id that has no
+    /// symbol, if any.
+    fn decode(&self, ids: &[Self::Id]) -> Result<Vec<&Self::Data>, UnknownId<Self::Id>> {
+        ids.iter().enumerate()
+            .map(|(position, id)| match self.get_symbol(id) {
+                Some(symbol) => Ok(symbol.data()),
+                None => Err(UnknownId { position, id: *id, }),
+            })
+            .collect()
+    }
+
+    /// Drops the symbols for which `f` returns `false` and renumbers the rest
+    /// densely, preserving their relative order, as `Table::retain()` does. The
+    /// index is updated to match.
+    ///
+    /// Returns a vector that maps each old id (as a `usize` index) to the new id
+    /// of its symbol, or `None` if the symbol was dropped.
+    fn retain<F>(&mut self, f: F) -> Vec<Option<Self::Id>>
+        where F: FnMut(&Symbol<Self::Data, Self::Id>) -> bool;
+
+    /// Remaps associations between data and ids, selectively dropping some, as
+    /// `Table::remap()` does. The index is updated to match. The caller is
+    /// responsible for producing a dense range of unique ids starting at 0.
+    fn remap<F>(&mut self, f: F)
+        where F: FnMut(&Symbol<Self::Data, Self::Id>) -> Option<Self::Id>;
+
+    /// Remaps associations between data and ids as `Table::try_remap()` does,
+    /// checking that the new ids form a dense range starting at 0. On failure,
+    /// the index and its table are left unchanged.
+    fn try_remap<F>(&mut self, f: F) -> Result<(), RemapError<Self::Id>>
+        where F: FnMut(&Symbol<Self::Data, Self::Id>) -> Option<Self::Id>;
+}
+
+/// Determines what `HashIndexing::align_to()` does with the ids of symbols
+/// that are no longer present.
+///
+/// Ids that are not given to any symbol are filled with tombstones: symbols
+/// that hold a clone of the placeholder value carried by the policy. They are
+/// not indexed, so looking up the placeholder value does not find them (see
+/// `HashIndexing::is_tombstone()`).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum AlignPolicy<T> {
+    /// Ids of symbols that are no longer present are never reused. New symbols
+    /// are given ids after all the ids of the reference indexing.
+    Tombstone(T),
+    /// Ids of symbols that are no longer present are given to new symbols,
+    /// lowest first. Any remaining new symbols are given ids after all the ids
+    /// of the reference indexing.
+    Reuse(T),
 }
 
 /// HashMap-backed table indexing.
+///
+/// Values are hashed with `S`, which defaults to the standard library's
+/// randomly-seeded SipHash. Any other `BuildHasher` may be plugged in with
+/// `with_hasher()`, for example a faster hasher for short strings or one with a
+/// fixed seed for deterministic tests.
+///
+/// An index created with `with_reserved()` starts with a set of reserved
+/// symbols, such as padding or sentence boundary markers, which have the ids
+/// `0..n` and are never dropped or renumbered by `retain()` or `remap()`.
 #[derive(Debug)]
-pub struct HashIndexing<T, D> where T: Eq + Hash, D: SymbolId {
+pub struct HashIndexing<T, D, S = RandomState> where T: Eq + Hash, D: SymbolId {
     table: Table<T, D>,
-    by_symbol: HashMap<Ref<T>, Ref<Symbol<T, D>>>,
-    by_id: Vec<Ref<Symbol<T, D>>>,
+    by_symbol: HashMap<Ref<T>, Ref<Symbol<T, D>>, S>,
+    /// Number of reserved symbols, which occupy the first ids in the table.
+    pub(crate) reserved: usize,
+    /// Sorted ids of the tombstones inserted by `align_to()`, which are in the
+    /// table but not in `by_symbol`.
+    pub(crate) tombstones: Vec<D>,
 }
 
-impl<T, D> Default for HashIndexing<T, D> where T: Eq + Hash, D: SymbolId {
+impl<T, D, S> Default for HashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
     fn default() -> Self {
-        HashIndexing {
-            table: Table::new(),
-            by_symbol: HashMap::new(),
-            by_id: Vec::new(),
-        }
+        HashIndexing::with_hasher(S::default())
     }
 }
 
-impl<T, D> Indexing for HashIndexing<T, D> where T: Eq + Hash, D: SymbolId {
+impl<T, D, S> Indexing for HashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
     type Data = T;
     type Id = D;
 
     fn from_table(table: Table<T, D>) -> Self {
-        let mut by_symbol = HashMap::with_capacity(table.len());
-        let mut by_id =
-            match table.iter().next() {
-                Some(symbol) => vec![Ref::new(symbol); table.len()],
-                None => Vec::new(),
-            };
-        for symbol in table.iter() {
-            by_symbol.insert(Ref::new(symbol.data()), Ref::new(symbol));
-            by_id[symbol.id().as_usize()] = Ref::new(symbol);
-        }
-        HashIndexing {
-            table: table,
-            by_symbol: by_symbol,
-            by_id: by_id,
-        }
+        HashIndexing::from_table_with_hasher(table, S::default())
     }
 
     fn table(&self) -> &Table<Self::Data, Self::Id> { &self.table }
 
     fn to_table(self) -> Table<Self::Data, Self::Id> { self.table }
 
-    fn get<'s>(&'s self, data: &T) -> Option<&'s Symbol<T, D>> {
+    fn get<Q>(&self, data: &Q) -> Option<&Symbol<T, D>> where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
         // Unsafe call to Ref::deref(): should be fine as because we own
         // self.table and the ref refers into that.
-        self.by_symbol.get(&Ref::new(data)).map(|x| unsafe { x.deref() })
+        self.by_symbol.get(&Query(data)).map(|x| unsafe { x.deref() })
     }
 
-    fn get_or_insert<'s>(&'s mut self, data: T) -> Insertion<&'s Symbol<T, D>> {
-        use std::collections::hash_map::Entry;
-        if let Entry::Occupied(e) = self.by_symbol.entry(Ref::new(&data)) {
-            // Unsafe call to Ref::deref(): should be fine as because we own
-            // self.table and the ref refers into that.
-            return Insertion::Present(unsafe { e.get().deref() })
+    fn get_or_insert(&mut self, data: T) -> Insertion<&Symbol<T, D>> {
+        match self.try_get_or_insert(data) {
+            Ok(insertion) => insertion,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_get_or_insert(&mut self, data: T)
+                         -> Result<Insertion<&Symbol<T, D>>, CapacityError<T>> {
+        match self.probe(&data) {
+            Probe::Occupied(symbol) => Ok(Insertion::Present(symbol)),
+            Probe::Vacant(slot) => slot.try_insert(data).map(Insertion::New),
         }
-        // TODO: when the HashMap API gets revised, we may be able to do this
-        // without a second hashtable lookup.
-        let symbol = self.table.insert(data);
-        // The Ref that gets inserted has to be backed by data in the table, not
-        // data on the stack (which is how we did the previous lookup).
-        self.by_symbol.insert(Ref::new(symbol.data()), Ref::new(symbol));
-        self.by_id.push(Ref::new(symbol));
-        Insertion::New(symbol)
     }
 
     fn get_symbol<'s>(&'s self, id: &D) -> Option<&'s Symbol<T, D>> {
-        self.by_id.get(id.as_usize()).map(|x| unsafe { x.deref() })
+        self.table.get(id)
+    }
+
+    /// Reserved symbols are always retained, and keep their ids.
+    fn retain<F>(&mut self, mut f: F) -> Vec<Option<D>> where F: FnMut(&Symbol<T, D>) -> bool {
+        // f is called before anything is modified, so that the index is left
+        // intact if it panics.
+        let reserved = self.reserved;
+        let keep: Vec<bool> = self.table.iter().rev()
+            .map(|symbol| symbol.id().as_usize() < reserved || f(symbol))
+            .collect();
+        let dropped = self.dropped_entries(self.table.iter().rev().zip(keep.iter())
+                                           .filter(|&(_, &keep)| !keep)
+                                           .map(|(symbol, _)| symbol));
+        let mut keep = keep.into_iter();
+        let translation = self.table.retain(|_| keep.next().unwrap());
+        // Retained symbols keep their order, so the tombstones stay sorted.
+        self.tombstones = self.tombstones.iter()
+            .filter_map(|id| translation[id.as_usize()])
+            .collect();
+        self.unindex(dropped);
+        translation
+    }
+
+    /// Reserved symbols can never be dropped or renumbered, so `f` must return
+    /// the current id of each of them and assign the other symbols ids starting
+    /// after the reserved ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` changes or drops the id of a reserved symbol. The index is
+    /// left unchanged in this case.
+    fn remap<F>(&mut self, f: F) where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let new_ids = match self.new_ids(f) {
+            Ok(new_ids) => new_ids,
+            Err(e) => panic!("{}", e),
+        };
+        let tombstones = self.remapped_tombstones(&new_ids);
+        let dropped = self.dropped_entries(self.table.iter().zip(new_ids.iter())
+                                           .filter(|&(_, id)| id.is_none())
+                                           .map(|(symbol, _)| symbol));
+        let mut new_ids = new_ids.into_iter();
+        self.table.remap(|_| new_ids.next().unwrap());
+        self.tombstones = tombstones;
+        self.unindex(dropped);
+    }
+
+    /// Returns an error if `f` changes or drops the id of a reserved symbol,
+    /// as well as if the new ids are not dense. The index is left unchanged in
+    /// either case.
+    fn try_remap<F>(&mut self, f: F) -> Result<(), RemapError<D>>
+        where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let new_ids = self.new_ids(f)?;
+        let tombstones = self.remapped_tombstones(&new_ids);
+        let dropped = self.dropped_entries(self.table.iter().zip(new_ids.iter())
+                                           .filter(|&(_, id)| id.is_none())
+                                           .map(|(symbol, _)| symbol));
+        let mut new_ids = new_ids.into_iter();
+        self.table.try_remap(|_| new_ids.next().unwrap())?;
+        self.tombstones = tombstones;
+        self.unindex(dropped);
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::{HashIndexing, Indexing, Insertion, Ref};
-    use ::{SymbolId, Table};
+impl<T, D> HashIndexing<T, D, RandomState> where T: Eq + Hash, D: SymbolId {
+    /// Creates a new, empty index that can hold at least `capacity` symbols
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashIndexing::with_capacity_and_hasher(capacity, RandomState::new())
+    }
 
-    use std::collections::hash_map::DefaultHasher;
-    use std::cmp::Ordering;
-    use std::hash::{Hash, Hasher};
-    use std::str::FromStr;
+    /// Creates a new index whose first symbols are the values in `reserved`,
+    /// which are assigned the ids `0..n` in order. Repeated values are only
+    /// inserted once.
+    ///
+    /// Reserved symbols are never dropped or renumbered; see `is_reserved()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use symbol_map::indexing::{HashIndexing, Indexing};
+    ///
+    /// let mut index = HashIndexing::<&str, usize>::with_reserved(&["<pad>", "<unk>"]);
+    /// index.get_or_insert("the");
+    /// index.retain(|symbol| symbol.data().len() < 3);
+    /// assert_eq!(index.get_symbol_id("<unk>"), Some(1));
+    /// assert!(index.is_reserved(&1));
+    /// assert!(!index.is_reserved(&2));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reserved` holds more distinct values than there are values of
+    /// `D`.
+    pub fn with_reserved(reserved: &[T]) -> Self where T: Clone {
+        HashIndexing::with_reserved_and_hasher(reserved, RandomState::new())
+    }
+}
 
-    const VALUES: &'static [usize] = &[101, 203, 500, 30, 0, 1];
+impl<T, D, S> HashIndexing<T, D, S> where T: Eq + Hash, D: SymbolId, S: BuildHasher {
+    /// Creates a new, empty index that hashes values with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        HashIndexing::with_capacity_and_hasher(0, hasher)
+    }
 
-    #[test]
-    fn ref_impls_ok() {
-        let x1 = String::from_str("foo").unwrap();
-        let x2 = String::from_str("foo").unwrap();
-        let x3 = String::from_str("fo").unwrap();
-        let x4 = String::from_str("fox").unwrap();
-        assert!(x1 == x2);
-        assert!(&x1 as *const String != &x2 as *const String);
+    /// Creates a new, empty index that hashes values with `hasher` and can hold
+    /// at least `capacity` symbols without reallocating.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        HashIndexing {
+            table: Table::with_capacity(capacity),
+            by_symbol: HashMap::with_capacity_and_hasher(capacity, hasher),
+            reserved: 0,
+            tombstones: Vec::new(),
+        }
+    }
 
-        let r1 = Ref::new(&x1);
-        let r2 = Ref::new(&x2);
-        let r3 = Ref::new(&x3);
-        let r4 = Ref::new(&x4);
-        // Eq, PartialEq.
-        assert_eq!(r1, r2);
-        assert!(r1 != r3);
-        assert!(r1 != r4);
+    /// Creates a new index that hashes values with `hasher` and whose first
+    /// symbols are the values in `reserved`, as `with_reserved()` does.
+    pub fn with_reserved_and_hasher(reserved: &[T], hasher: S) -> Self where T: Clone {
+        let mut index = HashIndexing::with_capacity_and_hasher(reserved.len(), hasher);
+        for value in reserved {
+            if let Probe::Vacant(slot) = index.probe(value) {
+                if let Err(e) = slot.try_insert(value.clone()) {
+                    panic!("{}", e);
+                }
+            }
+        }
+        index.reserved = index.table.len();
+        index
+    }
 
-        // Ord (skip PartialOrd).
-        assert_eq!(r1.cmp(&r2), Ordering::Equal);
-        assert_eq!(r1.cmp(&r3), Ordering::Greater);
-        assert_eq!(r1.cmp(&r4), Ordering::Less);
+    /// Converts the index into an immutable `FrozenHashIndexing` with a compact
+    /// layout optimized for lookups. Use `FrozenHashIndexing::thaw()` to convert
+    /// it back.
+    pub fn freeze(self) -> FrozenHashIndexing<T, D, S> where S: Clone {
+        let HashIndexing { table, by_symbol, reserved, tombstones } = self;
+        let hasher = by_symbol.hasher().clone();
+        // The Refs into the table must be dropped before it is consumed.
+        drop(by_symbol);
+        FrozenHashIndexing::new(table.into_iter().rev().collect(), hasher, reserved, tombstones)
+    }
 
-        // Hash.
-        let mut rh1 = DefaultHasher::new();
-        let mut rh2 = DefaultHasher::new();
-        let mut rh3 = DefaultHasher::new();
-        let mut rh4 = DefaultHasher::new();
-        r1.hash(&mut rh1);
-        r2.hash(&mut rh2);
-        r3.hash(&mut rh3);
-        r4.hash(&mut rh4);
-        let rh1 = rh1.finish();
-        let rh2 = rh2.finish();
-        let rh3 = rh3.finish();
-        let rh4 = rh4.finish();
+    /// Returns `true` iff `id` is the id of a reserved symbol, which can never
+    /// be dropped or renumbered.
+    pub fn is_reserved(&self, id: &D) -> bool {
+        id.as_usize() < self.reserved
+    }
 
-        let mut xh1 = DefaultHasher::new();
-        let mut xh2 = DefaultHasher::new();
-        let mut xh3 = DefaultHasher::new();
-        let mut xh4 = DefaultHasher::new();
-        x1.hash(&mut xh1);
-        x2.hash(&mut xh2);
-        x3.hash(&mut xh3);
-        x4.hash(&mut xh4);
-        let xh1 = xh1.finish();
-        let xh2 = xh2.finish();
-        let xh3 = xh3.finish();
-        let xh4 = xh4.finish();
+    /// Returns `true` iff `id` is the id of a tombstone inserted by
+    /// `align_to()`. Tombstones are in the table, but their values are not
+    /// indexed, so `get()` never returns them.
+    pub fn is_tombstone(&self, id: &D) -> bool {
+        self.tombstones.binary_search(id).is_ok()
+    }
 
-        assert_eq!(rh1, xh1);
-        assert_eq!(rh2, xh2);
-        assert_eq!(rh3, xh3);
-        assert_eq!(rh4, xh4);
+    /// Returns a new index that hashes values with `hasher` and has already
+    /// indexed the contents of `table`.
+    pub fn from_table_with_hasher(table: Table<T, D>, hasher: S) -> Self {
+        HashIndexing::from_parts(table, hasher, 0, Vec::new())
     }
 
-    #[test]
-    fn hash_indexing_empty_ok() {
-        let t = Table::<usize, usize>::new();
-        assert_eq!(t.len(), 0);
-        let i = HashIndexing::from_table(t);
-        assert!(i.by_symbol.is_empty());
-        assert!(i.by_id.is_empty());
+    /// Returns a new index that hashes values with `hasher` and has indexed
+    /// the contents of `table`, whose first `reserved` ids are reserved and
+    /// whose symbols with the sorted ids `tombstones` are tombstones.
+    pub(crate) fn from_parts(table: Table<T, D>, hasher: S, reserved: usize, tombstones: Vec<D>)
+                             -> Self {
+        let mut index = HashIndexing {
+            by_symbol: HashMap::with_capacity_and_hasher(table.len(), hasher),
+            table,
+            reserved,
+            tombstones,
+        };
+        index.index_table();
+        index
     }
 
-    #[test]
-    fn hash_indexing_from_table_ok() {
-        let mut t = Table::<usize, usize>::new();
-        for v in VALUES.iter() {
-            t.insert(*v);
+    /// Returns a reference to the index's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        self.by_symbol.hasher()
+    }
+
+    /// Returns the number of symbols the index can hold without reallocating
+    /// either its table or its hash map.
+    pub fn capacity(&self) -> usize {
+        cmp::min(self.table.capacity(), self.by_symbol.capacity())
+    }
+
+    /// Reserves capacity for at least `additional` more symbols to be inserted
+    /// without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.table.reserve(additional);
+        self.by_symbol.reserve(additional);
+    }
+
+    /// Shrinks the index's table and hash map as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.table.shrink_to_fit();
+        self.by_symbol.shrink_to_fit();
+    }
+
+    /// Renumbers the symbols of the index so that values which also occur in
+    /// `previous` keep the ids they have there, as far as possible. Reserved
+    /// symbols always keep their ids. Other symbols are given new ids in their
+    /// current order, according to `policy`, and tombstones are inserted so
+    /// that the ids remain dense. Tombstones left by an earlier call are
+    /// dropped first.
+    ///
+    /// Returns a vector that maps each old id (as a `usize` index) to the new id
+    /// of its symbol, or `None` if there was no symbol with that id or it was a
+    /// tombstone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use symbol_map::indexing::{AlignPolicy, HashIndexing, Indexing};
+    ///
+    /// let mut previous = HashIndexing::<&str, usize>::default();
+    /// previous.encode_or_insert(vec!["a", "b", "c"]);
+    /// let mut index = HashIndexing::<&str, usize>::default();
+    /// index.encode_or_insert(vec!["d", "c", "a"]);
+    /// let translation = index.align_to(&previous, AlignPolicy::Tombstone("<gone>"));
+    /// assert_eq!(translation, vec![Some(3), Some(2), Some(0)]);
+    /// assert_eq!(index.get_symbol(&1).unwrap().data(), &"<gone>");
+    /// assert!(index.is_tombstone(&1));
+    /// assert!(index.get(&"<gone>").is_none());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new ids, including tombstones, do not fit in `D`.
+    pub fn align_to<I>(&mut self, previous: &I, policy: AlignPolicy<T>) -> Vec<Option<D>>
+        where I: Indexing<Data = T, Id = D>, T: Clone {
+        // The ids of previous may have gaps, so this covers its largest id
+        // rather than its length.
+        let previous_bound = previous.table().iter()
+            .map(|symbol| symbol.id().as_usize() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut used = vec![false; cmp::max(previous_bound, self.reserved)];
+        // Pairs of old and new ids, in id order.
+        let mut new_ids: Vec<(usize, Option<usize>)> = Vec::with_capacity(self.table.len());
+        let mut unmatched = Vec::new();
+        for symbol in self.table.iter().rev() {
+            let old_id = symbol.id().as_usize();
+            let new_id = if self.is_reserved(symbol.id()) {
+                Some(old_id)
+            } else if self.is_tombstone(symbol.id()) {
+                None
+            } else {
+                match previous.get(symbol.data()) {
+                    Some(p) if !used[p.id().as_usize()] => Some(p.id().as_usize()),
+                    _ => {
+                        unmatched.push(new_ids.len());
+                        None
+                    },
+                }
+            };
+            if let Some(id) = new_id {
+                used[id] = true;
+            }
+            new_ids.push((old_id, new_id));
         }
-        let expected_len = t.len();
-        let expected_values: Vec<(usize, usize)> =
-            t.iter().map(|s| (*s.data(), *s.id())).collect();
 
-        let i = HashIndexing::from_table(t);
-        assert_eq!(i.by_symbol.len(), expected_len);
-        assert_eq!(i.by_id.len(), expected_len);
-        for (data, id) in expected_values.into_iter() {
-            let data_ref = Ref::new(&data);
-            unsafe {
-                assert_eq!(i.by_symbol.get(&data_ref).unwrap().deref().data(), &data);
-                assert_eq!(i.by_symbol.get(&data_ref).unwrap().deref().id(), &id);
-                assert_eq!(i.by_id[id.as_usize()].deref().data(), &data);
+        let (placeholder, reuse) = match policy {
+            AlignPolicy::Tombstone(placeholder) => (placeholder, false),
+            AlignPolicy::Reuse(placeholder) => (placeholder, true),
+        };
+        let mut free = (0..used.len()).filter(|&id| !used[id]);
+        let mut next_id = used.len();
+        for i in unmatched {
+            new_ids[i].1 = Some(match free.next().filter(|_| reuse) {
+                Some(id) => id,
+                None => {
+                    next_id += 1;
+                    next_id - 1
+                },
+            });
+        }
+
+        // Fill every unused id below the largest one assigned.
+        let top = new_ids.iter().filter_map(|&(_, id)| id).map(|id| id + 1).max().unwrap_or(0);
+        let mut assigned = vec![false; top];
+        for id in new_ids.iter().filter_map(|&(_, id)| id) {
+            assigned[id] = true;
+        }
+        let tombstones: Vec<usize> = (0..top).filter(|&id| !assigned[id]).collect();
+
+        let mut ids = Vec::with_capacity(top);
+        let mut id = Some(D::default());
+        for _ in 0..top {
+            match id {
+                Some(d) => ids.push(d),
+                None => panic!("no symbol ids left to assign"),
             }
+            id = id.and_then(|id| id.checked_next());
+        }
+        let mut translation: Vec<Option<D>> = vec![None; self.table.id_bound()];
+        for (old_id, new_id) in new_ids {
+            translation[old_id] = new_id.map(|id| ids[id]);
         }
-    }
 
-    #[test]
-    fn hash_indexing_empty_insertion_ok() {
-        let mut i = HashIndexing::<usize, usize>::default();
+        // The symbols that are kept are first numbered densely in id order,
+        // dropping the old tombstones, so that the new tombstones fit in the
+        // ids after them. Everything is then moved into place. Symbols keep
+        // their addresses when they are renumbered, so by_symbol stays valid.
+        let mut dense_ids: Vec<Option<D>> = vec![None; translation.len()];
+        let mut final_ids: Vec<D> = Vec::with_capacity(top);
+        for (old_id, new_id) in translation.iter().enumerate() {
+            if let Some(new_id) = *new_id {
+                dense_ids[old_id] = Some(ids[final_ids.len()]);
+                final_ids.push(new_id);
+            }
+        }
+        final_ids.extend(tombstones.iter().map(|&id| ids[id]));
+        self.table.remap(|symbol| dense_ids[symbol.id().as_usize()]);
+        self.tombstones.clear();
+        for _ in 0..tombstones.len() {
+            let id = *self.table.insert(placeholder.clone()).id();
+            self.tombstones.push(id);
+        }
+        self.table.remap(|symbol| Some(final_ids[symbol.id().as_usize()]));
+        self.tombstones = tombstones.into_iter().map(|id| ids[id]).collect();
+        translation
+    }
 
-        for v in VALUES.iter() {
-            assert!(i.get(v).is_none());
-            let id = match i.get_or_insert(*v) {
-                Insertion::Present(_) => panic!(),
-                Insertion::New(symbol) => {
-                    assert_eq!(symbol.data(), v);
-                    *symbol.id()
+    /// Absorbs the symbols of `other` into the index, as `Table::merge()` does.
+    /// Values already present in the index are dropped rather than cloned, and
+    /// the others are inserted in id order. Tombstones in `other` are dropped.
+    ///
+    /// Returns a vector that maps each id in `other` (as a `usize` index) to
+    /// the id of the same value in the merged index, or `None` if that id was
+    /// not assigned or was a tombstone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every value of `D` is assigned before all new values have been
+    /// inserted. In this case, the index is left unchanged.
+    pub fn merge<R>(&mut self, other: HashIndexing<T, D, R>) -> Vec<Option<D>>
+        where R: BuildHasher {
+        let HashIndexing { table, tombstones, .. } = other;
+        let mut translation = vec![None; table.id_bound()];
+        let mut symbols: Vec<Symbol<T, D>> = table.into_iter()
+            .filter(|symbol| tombstones.binary_search(symbol.id()).is_err())
+            .collect();
+        symbols.sort_unstable_by_key(|symbol| *symbol.id());
+        // Ids are checked before anything is inserted, so that the index is
+        // left unchanged if they run out.
+        self.check_ids_left(symbols.iter().map(Symbol::data));
+        self.reserve(symbols.len());
+        for symbol in symbols {
+            let old_id = symbol.id().as_usize();
+            let id = match self.probe(symbol.data()) {
+                Probe::Occupied(symbol) => *symbol.id(),
+                Probe::Vacant(slot) => match slot.try_insert(symbol.into_data()) {
+                    Ok(symbol) => *symbol.id(),
+                    Err(e) => panic!("{}", e),
                 },
             };
-            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+            translation[old_id] = Some(id);
         }
+        translation
     }
 
-    
-    #[test]
-    fn indexed_present_ok() {
-        let mut t = Table::<usize, usize>::new();
-        for v in VALUES.iter() {
-            t.insert(*v);
+    /// Looks up each value produced by `values` in the index, inserting the
+    /// ones that aren't present, and returns a summary of the symbols created.
+    /// Repeated values are only inserted once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table runs out of ids, as `Indexing::get_or_insert()`
+    /// does. Values inserted before that point remain in the index.
+    pub fn get_or_insert_all<I>(&mut self, values: I) -> Extension<D>
+        where I: IntoIterator<Item = T> {
+        let mut extension = Extension::new();
+        for value in values {
+            if let Probe::Vacant(slot) = self.probe(&value) {
+                match slot.try_insert(value) {
+                    Ok(symbol) => extension.push(*symbol.id()),
+                    Err(e) => panic!("{}", e),
+                }
+            }
         }
+        extension
+    }
 
-        let mut i = HashIndexing::from_table(t);
-        for v in VALUES.iter() {
-            assert_eq!(i.get(v).unwrap().data(), v);
-            let id = match i.get_or_insert(*v) {
-                Insertion::New(_) => panic!(),
-                Insertion::Present(symbol) => {
-                    assert_eq!(symbol.data(), v);
-                    *symbol.id()
-                },
-            };
-            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+    /// Looks up `data` in the index, inserting a clone of it made with
+    /// `to_owned()` if it isn't present. The clone is only made when a new
+    /// table entry has to be created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use symbol_map::indexing::{HashIndexing, Indexing};
+    ///
+    /// let mut index = HashIndexing::<String, usize>::default();
+    /// for token in "a rose is a rose".split(' ') {
+    ///     index.get_or_insert_borrowed(token);
+    /// }
+    /// assert_eq!(index.table().len(), 3);
+    /// assert_eq!(index.get_symbol_id("rose"), Some(1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `D` has already
+    /// been assigned.
+    pub fn get_or_insert_borrowed<Q>(&mut self, data: &Q) -> Insertion<&Symbol<T, D>>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash + ToOwned<Owned = T> {
+        self.get_or_insert_with(data, || data.to_owned())
+    }
+
+    /// Looks up `data` in the index, inserting the value returned by `f` if it
+    /// isn't present. `f` is only called when a new table entry has to be
+    /// created, and must return a value that is equal to `data` when borrowed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `D` has already
+    /// been assigned. Use `try_get_or_insert_with()` to handle this case.
+    pub fn get_or_insert_with<Q, F>(&mut self, data: &Q, f: F) -> Insertion<&Symbol<T, D>>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash, F: FnOnce() -> T {
+        match self.try_get_or_insert_with(data, f) {
+            Ok(insertion) => insertion,
+            Err(e) => panic!("{}", e),
         }
     }
 
-    #[test]
-    fn send_to_thread_safe_ok() {
-        use std::sync::Arc;
-        use std::thread;
+    /// Looks up `data` in the index, inserting the value returned by `f` as
+    /// `get_or_insert_with()` does. If a new table entry is needed but every
+    /// value of `D` has already been assigned, returns an error holding the
+    /// value returned by `f` instead.
+    pub fn try_get_or_insert_with<Q, F>(&mut self, data: &Q, f: F)
+                                        -> Result<Insertion<&Symbol<T, D>>, CapacityError<T>>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash, F: FnOnce() -> T {
+        match self.entry(data) {
+            Entry::Occupied(e) => Ok(Insertion::Present(e.get())),
+            Entry::Vacant(e) => e.try_insert(f()).map(Insertion::New),
+        }
+    }
+
+    /// Looks up `data` in the index and returns an `Entry` that may be used to
+    /// inspect the symbol if it is present, or to insert it if not. Only one
+    /// hash table probe is made, whichever way the entry is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use symbol_map::indexing::{Entry, HashIndexing, Indexing};
+    ///
+    /// let mut index = HashIndexing::<String, usize>::default();
+    /// index.entry("NNP").or_insert();
+    /// let id = *index.entry("VBD").or_insert_with(|| "VBD".to_string()).id();
+    /// assert_eq!(id, 1);
+    ///
+    /// // Reject values that are not already present.
+    /// match index.entry("JJ") {
+    ///     Entry::Occupied(e) => println!("known word {}", e.get().id()),
+    ///     Entry::Vacant(e) => println!("unknown word {}", e.key()),
+    /// }
+    /// assert_eq!(index.table().len(), 2);
+    /// ```
+    pub fn entry<'q, Q>(&mut self, data: &'q Q) -> Entry<'_, 'q, Q, T, D, S>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match self.probe(data) {
+            Probe::Occupied(symbol) => Entry::Occupied(OccupiedEntry { key: data, symbol, }),
+            Probe::Vacant(slot) => Entry::Vacant(VacantEntry { key: data, slot, }),
+        }
+    }
+
+    /// Hashes `data` and finds either its symbol or the slot in `by_symbol`
+    /// where it belongs. The result does not borrow `data`.
+    fn probe<Q>(&mut self, data: &Q) -> Probe<'_, T, D, S> where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        // Ref<T> hashes as the T that it points to, which hashes the same as
+        // data because T: Borrow<Q>.
+        let hash = self.by_symbol.hasher().hash_one(data);
+        let entry = self.by_symbol.raw_entry_mut()
+            .from_hash(hash, |key| unsafe { key.deref() }.borrow() == data);
+        match entry {
+            // Unsafe call to Ref::deref(): should be fine as because we own
+            // self.table and the ref refers into that.
+            RawEntryMut::Occupied(e) => Probe::Occupied(unsafe { e.get().deref() }),
+            RawEntryMut::Vacant(entry) =>
+                Probe::Vacant(VacantSlot { hash, table: &mut self.table, entry, }),
+        }
+    }
+
+    /// Adds every symbol in `self.table` except the tombstones to
+    /// `self.by_symbol`.
+    fn index_table(&mut self) {
+        let tombstones = &self.tombstones;
+        for symbol in self.table.iter() {
+            if tombstones.binary_search(symbol.id()).is_err() {
+                self.by_symbol.insert(Ref::new(symbol.data()), Ref::new(symbol));
+            }
+        }
+    }
+
+    /// Returns the hash and address of the value of each of `symbols`, which
+    /// are about to be dropped from `self.table`, for `unindex()`.
+    fn dropped_entries<'a, I>(&self, symbols: I) -> Vec<(u64, *const T)>
+        where I: Iterator<Item = &'a Symbol<T, D>>, T: 'a, D: 'a {
+        let hasher = self.by_symbol.hasher();
+        symbols.map(|symbol| (hasher.hash_one(symbol.data()), symbol.data() as *const T)).collect()
+    }
+
+    /// Removes the entries of `self.by_symbol` for values whose symbols have
+    /// been dropped from `self.table`, given by `dropped_entries()`. Retained
+    /// symbols don't move when the table is renumbered, so the other entries
+    /// remain valid.
+    fn unindex(&mut self, dropped: Vec<(u64, *const T)>) {
+        for (hash, data) in dropped {
+            // Entries are matched by address, without dereferencing the
+            // dropped values. Tombstones have no entry, so none is found.
+            let entry = self.by_symbol.raw_entry_mut().from_hash(hash, |key| key.ptr == data);
+            if let RawEntryMut::Occupied(entry) = entry {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Panics if there are not enough ids left to insert every value produced
+    /// by `values` that is not already in the index. Repeated values only need
+    /// one id.
+    fn check_ids_left<'a, I>(&self, values: I) where I: IntoIterator<Item = &'a T>, T: 'a {
+        let mut new_values = HashSet::new();
+        let mut next_id = self.table.next_id();
+        for value in values {
+            if !self.by_symbol.contains_key(&Query(value)) && new_values.insert(value) {
+                next_id = match next_id {
+                    Some(id) => id.checked_next(),
+                    None => panic!("no symbol ids left to assign"),
+                };
+            }
+        }
+    }
+
+    /// Calls `f` on every symbol in the order of `Table::iter()` and collects
+    /// the new ids it returns. Nothing is modified, so a remapping can validate
+    /// the ids, and a panic in `f` leaves the index intact.
+    ///
+    /// Returns an error if `f` changes or drops the id of a reserved symbol.
+    fn new_ids<F>(&self, f: F) -> Result<Vec<Option<D>>, RemapError<D>>
+        where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let new_ids: Vec<Option<D>> = self.table.iter().map(f).collect();
+        let mut changed: Vec<D> = self.table.iter().zip(new_ids.iter())
+            .filter(|&(symbol, new_id)| {
+                self.is_reserved(symbol.id()) && new_id.as_ref() != Some(symbol.id())
+            })
+            .map(|(symbol, _)| *symbol.id())
+            .collect();
+        if changed.is_empty() {
+            return Ok(new_ids)
+        }
+        changed.sort();
+        let len = new_ids.iter().filter(|id| id.is_some()).count();
+        Err(RemapError::reserved_changed(len, changed))
+    }
+
+    /// Returns the sorted ids that the tombstones will have once the symbols
+    /// are given `new_ids`, which are in the order of `Table::iter()`.
+    fn remapped_tombstones(&self, new_ids: &[Option<D>]) -> Vec<D> {
+        let mut tombstones: Vec<D> = self.table.iter().zip(new_ids.iter())
+            .filter(|&(symbol, _)| self.is_tombstone(symbol.id()))
+            .filter_map(|(_, new_id)| *new_id)
+            .collect();
+        tombstones.sort();
+        tombstones
+    }
+}
+
+impl<T, D, S> FromIterator<T> for HashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
+    fn from_iter<I>(values: I) -> Self where I: IntoIterator<Item = T> {
+        let mut index = HashIndexing::default();
+        index.get_or_insert_all(values);
+        index
+    }
+}
+
+impl<T, D, S> Extend<T> for HashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher {
+    fn extend<I>(&mut self, values: I) where I: IntoIterator<Item = T> {
+        self.get_or_insert_all(values);
+    }
+}
+
+/// The result of probing `HashIndexing::by_symbol` for a value.
+enum Probe<'a, T, D, S> where T: 'a + Eq + Hash, D: 'a + SymbolId, S: 'a {
+    Occupied(&'a Symbol<T, D>),
+    Vacant(VacantSlot<'a, T, D, S>),
+}
+
+/// The place in a `HashIndexing` where a value that is not yet present
+/// belongs.
+struct VacantSlot<'a, T, D, S> where T: 'a + Eq + Hash, D: 'a + SymbolId, S: 'a {
+    hash: u64,
+    table: &'a mut Table<T, D>,
+    entry: RawVacantEntryMut<'a, Ref<T>, Ref<Symbol<T, D>>, S>,
+}
+
+impl<'a, T, D, S> VacantSlot<'a, T, D, S>
+    where T: 'a + Eq + Hash, D: 'a + SymbolId, S: 'a + BuildHasher {
+    /// Inserts `value` into the table and then into the slot.
+    fn try_insert(self, value: T) -> Result<&'a Symbol<T, D>, CapacityError<T>> {
+        let symbol = self.table.try_insert(value)?;
+        // The Ref that gets inserted has to be backed by data in the table, not
+        // the data that was probed for.
+        self.entry.insert_hashed_nocheck(self.hash, Ref::new(symbol.data()), Ref::new(symbol));
+        Ok(symbol)
+    }
+}
+
+/// A view into a single value's place in a `HashIndexing`, which may or may
+/// not hold a symbol. Returned by `HashIndexing::entry()`.
+pub enum Entry<'a, 'q, Q, T, D, S = RandomState>
+    where Q: 'q + ?Sized, T: 'a + Eq + Hash, D: 'a + SymbolId, S: 'a {
+    /// The value is already present in the index.
+    Occupied(OccupiedEntry<'a, 'q, Q, T, D>),
+    /// The value is not present in the index.
+    Vacant(VacantEntry<'a, 'q, Q, T, D, S>),
+}
+
+impl<'a, 'q, Q, T, D, S> Entry<'a, 'q, Q, T, D, S>
+    where Q: 'q + ?Sized + Eq, T: 'a + Eq + Hash + Borrow<Q>, D: 'a + SymbolId,
+          S: 'a + BuildHasher {
+    /// Returns the value that was looked up.
+    pub fn key(&self) -> &'q Q {
+        match *self {
+            Entry::Occupied(ref e) => e.key(),
+            Entry::Vacant(ref e) => e.key(),
+        }
+    }
+
+    /// Calls `f` with the symbol if the value is present, then returns the
+    /// entry unchanged.
+    pub fn and_modify<F>(self, f: F) -> Self where F: FnOnce(&Symbol<T, D>) {
+        if let Entry::Occupied(ref e) = self {
+            f(e.get());
+        }
+        self
+    }
+
+    /// Returns the symbol if the value is present, else inserts a clone of the
+    /// value made with `to_owned()` and returns the new symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `D` has already
+    /// been assigned.
+    pub fn or_insert(self) -> &'a Symbol<T, D> where Q: ToOwned<Owned = T> {
+        let key = self.key();
+        self.or_insert_with(|| key.to_owned())
+    }
+
+    /// Returns the symbol if the value is present, else inserts the value
+    /// returned by `f` and returns the new symbol. `f` must return a value
+    /// that is equal to `key()` when borrowed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `D` has already
+    /// been assigned. Use `or_try_insert_with()` to handle this case.
+    pub fn or_insert_with<F>(self, f: F) -> &'a Symbol<T, D> where F: FnOnce() -> T {
+        match self.or_try_insert_with(f) {
+            Ok(symbol) => symbol,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Returns the symbol if the value is present, else inserts the value
+    /// returned by `f` as `or_insert_with()` does. If every value of `D` has
+    /// already been assigned, returns an error holding the value returned by
+    /// `f` instead.
+    pub fn or_try_insert_with<F>(self, f: F) -> Result<&'a Symbol<T, D>, CapacityError<T>>
+        where F: FnOnce() -> T {
+        match self {
+            Entry::Occupied(e) => Ok(e.get()),
+            Entry::Vacant(e) => e.try_insert(f()),
+        }
+    }
+}
+
+/// A view into a `HashIndexing` for a value that is already present.
+pub struct OccupiedEntry<'a, 'q, Q, T, D>
+    where Q: 'q + ?Sized, T: 'a + Eq + Hash, D: 'a + SymbolId {
+    key: &'q Q,
+    symbol: &'a Symbol<T, D>,
+}
+
+impl<'a, 'q, Q, T, D> OccupiedEntry<'a, 'q, Q, T, D>
+    where Q: 'q + ?Sized, T: 'a + Eq + Hash, D: 'a + SymbolId {
+    /// Returns the value that was looked up.
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// Returns the symbol for the value.
+    pub fn get(&self) -> &'a Symbol<T, D> {
+        self.symbol
+    }
+}
+
+/// A view into a `HashIndexing` for a value that is not present.
+pub struct VacantEntry<'a, 'q, Q, T, D, S = RandomState>
+    where Q: 'q + ?Sized, T: 'a + Eq + Hash, D: 'a + SymbolId, S: 'a {
+    key: &'q Q,
+    slot: VacantSlot<'a, T, D, S>,
+}
+
+impl<'a, 'q, Q, T, D, S> VacantEntry<'a, 'q, Q, T, D, S>
+    where Q: 'q + ?Sized + Eq, T: 'a + Eq + Hash + Borrow<Q>, D: 'a + SymbolId,
+          S: 'a + BuildHasher {
+    /// Returns the value that was looked up.
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// Inserts `value`, which must be equal to `key()` when borrowed, and
+    /// returns its new symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every value of `D` has already been assigned. Use
+    /// `try_insert()` to handle this case.
+    pub fn insert(self, value: T) -> &'a Symbol<T, D> {
+        match self.try_insert(value) {
+            Ok(symbol) => symbol,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Inserts `value` as `insert()` does. If every value of `D` has already
+    /// been assigned, returns an error holding `value` instead.
+    pub fn try_insert(self, value: T) -> Result<&'a Symbol<T, D>, CapacityError<T>> {
+        debug_assert!(value.borrow() == self.key, "value inserted does not match lookup key");
+        self.slot.try_insert(value)
+    }
+}
+
+/// Marks an unoccupied slot in a `FrozenHashIndexing`.
+const EMPTY_SLOT: usize = usize::MAX;
+
+/// A slot in the open-addressing table of a `FrozenHashIndexing`.
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    hash: u64,
+    /// Position of the symbol in `FrozenHashIndexing::symbols`, or
+    /// `EMPTY_SLOT`.
+    index: usize,
+}
+
+/// An immutable, read-optimized form of `HashIndexing`, created with
+/// `HashIndexing::freeze()`.
+///
+/// Symbols are stored in a single vector in id order, and values are looked up
+/// in a flat open-addressing table that holds each symbol's precomputed hash.
+/// No symbols can be added or removed, and because there are no pointers into
+/// the symbols, the index is `Sync` whenever its contents are, without relying
+/// on `Ref`.
+///
+/// # Example
+///
+/// ```
+/// use symbol_map::indexing::{HashIndexing, Indexing};
+///
+/// let mut index = HashIndexing::<String, usize>::default();
+/// index.encode_or_insert(vec!["NNP".to_string(), "VBD".to_string()]);
+/// let frozen = index.freeze();
+/// assert_eq!(*frozen.get("VBD").unwrap().id(), 1);
+/// assert_eq!(frozen.get_symbol(&0).unwrap().data(), "NNP");
+/// let mut index = frozen.thaw();
+/// index.get_or_insert("DT".to_string());
+/// ```
+#[derive(Debug)]
+pub struct FrozenHashIndexing<T, D, S = RandomState> where D: SymbolId {
+    symbols: Vec<Symbol<T, D>>,
+    /// Open-addressing table with linear probing. Its length is a power of two
+    /// that is more than twice the number of symbols.
+    slots: Vec<Slot>,
+    hasher: S,
+    reserved: usize,
+    /// Sorted ids of tombstones, which are not in `slots`.
+    tombstones: Vec<D>,
+}
+
+impl<T, D, S> FrozenHashIndexing<T, D, S> where T: Eq + Hash, D: SymbolId, S: BuildHasher {
+    /// Builds the lookup table for `symbols`, which must be in id order,
+    /// leaving out the `tombstones`.
+    fn new(symbols: Vec<Symbol<T, D>>, hasher: S, reserved: usize, tombstones: Vec<D>) -> Self {
+        let empty = Slot { hash: 0, index: EMPTY_SLOT, };
+        let mut slots = vec![empty; (2 * symbols.len() + 1).next_power_of_two()];
+        let mask = slots.len() - 1;
+        for (index, symbol) in symbols.iter().enumerate() {
+            if tombstones.binary_search(symbol.id()).is_ok() {
+                continue
+            }
+            let hash = hasher.hash_one(symbol.data());
+            let mut i = hash as usize & mask;
+            while slots[i].index != EMPTY_SLOT {
+                i = (i + 1) & mask;
+            }
+            slots[i] = Slot { hash, index, };
+        }
+        FrozenHashIndexing { symbols, slots, hasher, reserved, tombstones, }
+    }
+
+    /// Returns the number of symbols in the index.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns `true` iff the index contains no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns a reference to the index's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Looks up `data` in the index, as `Indexing::get()` does. Returns
+    /// `Some(&symbol)` if a symbol is present, else `None`.
+    pub fn get<Q>(&self, data: &Q) -> Option<&Symbol<T, D>>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let hash = self.hasher.hash_one(data);
+        let mask = self.slots.len() - 1;
+        let mut i = hash as usize & mask;
+        loop {
+            let slot = self.slots[i];
+            if slot.index == EMPTY_SLOT {
+                return None
+            }
+            let symbol = &self.symbols[slot.index];
+            if slot.hash == hash && symbol.data().borrow() == data {
+                return Some(symbol)
+            }
+            i = (i + 1) & mask;
+        }
+    }
+
+    /// Looks up `data` in the index, as `get()` does. Returns `Some(id)` with
+    /// the id of its symbol if a symbol is present, else `None`.
+    pub fn get_symbol_id<Q>(&self, data: &Q) -> Option<D>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get(data).map(|symbol| *symbol.id())
+    }
+
+    /// Looks up the symbol with id `id` in the index. Returns `Some(symbol)` if
+    /// a symbol is present, else `None`.
+    pub fn get_symbol(&self, id: &D) -> Option<&Symbol<T, D>> {
+        match self.symbols.get(id.as_usize()) {
+            Some(symbol) if symbol.id() == id => Some(symbol),
+            // The ids have gaps (see `Table::remap()`), so the symbol is not at
+            // the position given by its id, but the symbols are still sorted.
+            _ => self.symbols.binary_search_by(|symbol| symbol.id().cmp(id))
+                .ok()
+                .map(|i| &self.symbols[i]),
+        }
+    }
+
+    /// Returns `true` iff `id` is the id of a reserved symbol, as
+    /// `HashIndexing::is_reserved()` does.
+    pub fn is_reserved(&self, id: &D) -> bool {
+        id.as_usize() < self.reserved
+    }
+
+    /// Returns `true` iff `id` is the id of a tombstone, as
+    /// `HashIndexing::is_tombstone()` does.
+    pub fn is_tombstone(&self, id: &D) -> bool {
+        self.tombstones.binary_search(id).is_ok()
+    }
+
+    /// Converts the index back into a mutable `HashIndexing` with the same
+    /// symbols, ids, reserved symbols, tombstones and hasher.
+    pub fn thaw(self) -> HashIndexing<T, D, S> {
+        let mut table: Table<T, D> = Table::with_capacity(self.symbols.len());
+        let mut ids = Vec::with_capacity(self.symbols.len());
+        for symbol in self.symbols {
+            ids.push(*symbol.id());
+            table.insert(symbol.into_data());
+        }
+        // Reinserting the symbols in id order reassigns the same ids unless
+        // they have gaps, in which case the original ids are restored.
+        if ids.iter().enumerate().any(|(i, id)| id.as_usize() != i) {
+            table.remap(|symbol| Some(ids[symbol.id().as_usize()]));
+        }
+        HashIndexing::from_parts(table, self.hasher, self.reserved, self.tombstones)
+    }
+}
+
+/// Default number of shards in a `ConcurrentHashIndexing`.
+const DEFAULT_SHARDS: usize = 16;
+
+/// Type of the shards of a `ConcurrentHashIndexing`.
+type Shard<T, D, S> = RwLock<HashMap<Ref<T>, Ref<Symbol<T, D>>, S>>;
+
+/// Acquires a read lock. Locks are never held while the data they protect is
+/// in an inconsistent state, so a poisoned lock is used as is.
+fn read<X>(lock: &RwLock<X>) -> RwLockReadGuard<'_, X> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires a write lock, as `read()` does.
+fn write<X>(lock: &RwLock<X>) -> RwLockWriteGuard<'_, X> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// HashMap-backed table indexing that can be shared between threads, all of
+/// which may insert symbols through a shared reference.
+///
+/// Values are spread across several independently locked shards by their hash,
+/// so threads looking up or inserting different values rarely contend, and
+/// lookups of values that are already present only take a read lock. The
+/// underlying table is only ever appended to while the index is shared, so
+/// references to symbols remain valid while other threads insert.
+///
+/// Each shard hashes with a clone of `S`, so clones of `S` must produce the
+/// same hashes, as `RandomState` and `BuildHasherDefault` do.
+///
+/// # Example
+///
+/// ```
+/// extern crate crossbeam;
+/// # extern crate symbol_map;
+/// use symbol_map::indexing::ConcurrentHashIndexing;
+///
+/// # fn main() {
+/// let index = ConcurrentHashIndexing::<String, usize>::default();
+/// crossbeam::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| {
+///             for word in "the cat saw the dog".split(' ') {
+///                 index.get_or_insert(word.to_string());
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(index.len(), 4);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentHashIndexing<T, D, S = RandomState> where T: Eq + Hash, D: SymbolId {
+    table: RwLock<Table<T, D>>,
+    shards: Vec<Shard<T, D, S>>,
+    hasher: S,
+}
+
+impl<T, D> ConcurrentHashIndexing<T, D, RandomState> where T: Eq + Hash, D: SymbolId {
+    /// Creates a new, empty index with `shards` shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is 0.
+    pub fn with_shards(shards: usize) -> Self {
+        ConcurrentHashIndexing::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<T, D, S> ConcurrentHashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Clone {
+    /// Creates a new, empty index that hashes values with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        ConcurrentHashIndexing::with_shards_and_hasher(DEFAULT_SHARDS, hasher)
+    }
+
+    /// Creates a new, empty index with `shards` shards that hashes values with
+    /// `hasher`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is 0.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "a ConcurrentHashIndexing needs at least one shard");
+        ConcurrentHashIndexing {
+            table: RwLock::new(Table::new()),
+            shards: (0..shards).map(|_| RwLock::new(HashMap::with_hasher(hasher.clone()))).collect(),
+            hasher,
+        }
+    }
+
+    /// Returns a new index that hashes values with `hasher` and has already
+    /// indexed the contents of `table`.
+    pub fn from_table_with_hasher(table: Table<T, D>, hasher: S) -> Self {
+        let mut index = ConcurrentHashIndexing::with_hasher(hasher);
+        for symbol in table.iter() {
+            let hash = index.hasher.hash_one(symbol.data());
+            let shard = index.shard_index(hash);
+            index.shards[shard].get_mut().unwrap_or_else(PoisonError::into_inner)
+                .insert(Ref::new(symbol.data()), Ref::new(symbol));
+        }
+        // Moving the table does not move the symbols that the shards point to.
+        index.table = RwLock::new(table);
+        index
+    }
+
+    /// Returns a new index that has already indexed the contents of `table`.
+    pub fn from_table(table: Table<T, D>) -> Self where S: Default {
+        ConcurrentHashIndexing::from_table_with_hasher(table, S::default())
+    }
+
+    /// Extracts the underlying table from the index.
+    pub fn to_table(self) -> Table<T, D> {
+        // The Refs into the table must be dropped before it is handed out.
+        drop(self.shards);
+        self.table.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of symbols in the index.
+    pub fn len(&self) -> usize {
+        read(&self.table).len()
+    }
+
+    /// Returns `true` iff the index contains no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the index's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Looks up `data` in the index, as `Indexing::get()` does. Returns
+    /// `Some(&symbol)` if a symbol is present, else `None`.
+    pub fn get<Q>(&self, data: &Q) -> Option<&Symbol<T, D>>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let hash = self.hasher.hash_one(data);
+        let shard = read(&self.shards[self.shard_index(hash)]);
+        shard.raw_entry()
+            .from_hash(hash, |key| unsafe { key.deref() }.borrow() == data)
+            // Unsafe call to Ref::deref(): symbols are never moved or dropped
+            // while the index is borrowed, since that requires &mut self.
+            .map(|(_, symbol)| unsafe { symbol.deref() })
+    }
+
+    /// Looks up `data` in the index, as `get()` does. Returns `Some(id)` with
+    /// the id of its symbol if a symbol is present, else `None`.
+    pub fn get_symbol_id<Q>(&self, data: &Q) -> Option<D>
+        where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get(data).map(|symbol| *symbol.id())
+    }
+
+    /// Looks up `data` in the index, inserting it if it isn't present, as
+    /// `Indexing::get_or_insert()` does. Unlike that method, this only needs a
+    /// shared reference, so it may be called from several threads at once. A
+    /// value inserted concurrently by several threads is only inserted once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new table entry is needed but every value of `D` has
+    /// already been assigned. Use `try_get_or_insert()` to handle this case.
+    pub fn get_or_insert(&self, data: T) -> Insertion<&Symbol<T, D>> {
+        match self.try_get_or_insert(data) {
+            Ok(insertion) => insertion,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Looks up `data` in the index, inserting it as `get_or_insert()` does. If
+    /// a new table entry is needed but every value of `D` has already been
+    /// assigned, returns an error holding `data` instead.
+    pub fn try_get_or_insert(&self, data: T)
+                             -> Result<Insertion<&Symbol<T, D>>, CapacityError<T>> {
+        if let Some(symbol) = self.get(&data) {
+            return Ok(Insertion::Present(symbol))
+        }
+        let hash = self.hasher.hash_one(&data);
+        let mut shard = write(&self.shards[self.shard_index(hash)]);
+        // Another thread may have inserted data since the lookup above.
+        match shard.raw_entry_mut().from_hash(hash, |key| unsafe { key.deref() } == &data) {
+            RawEntryMut::Occupied(e) => Ok(Insertion::Present(unsafe { e.get().deref() })),
+            RawEntryMut::Vacant(entry) => {
+                // The table lock is only held while appending, and always
+                // after a shard lock, so it cannot deadlock.
+                let symbol = Ref::new(write(&self.table).try_insert(data)?);
+                let symbol = unsafe { symbol.deref() };
+                entry.insert_hashed_nocheck(hash, Ref::new(symbol.data()), Ref::new(symbol));
+                Ok(Insertion::New(symbol))
+            },
+        }
+    }
+
+    /// Looks up the symbol with id `id` in the index. Returns `Some(symbol)` if
+    /// a symbol is present, else `None`.
+    pub fn get_symbol(&self, id: &D) -> Option<&Symbol<T, D>> {
+        read(&self.table).get(id).map(|symbol| unsafe { Ref::new(symbol).deref() })
+    }
+
+    /// Returns the position in `self.shards` of the shard for values with hash
+    /// `hash`.
+    fn shard_index(&self, hash: u64) -> usize {
+        // The low bits of the hash pick a bucket within the shard, so the high
+        // bits are used to pick the shard.
+        (hash >> 32) as usize % self.shards.len()
+    }
+}
+
+impl<T, D, S> Default for ConcurrentHashIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Clone + Default {
+    fn default() -> Self {
+        ConcurrentHashIndexing::with_hasher(S::default())
+    }
+}
+
+/// An index for high-throughput ingestion, in which each worker thread interns
+/// values into its own local `HashIndexing` shard and the shards are
+/// periodically merged into one global index.
+///
+/// Local shards assign provisional ids. `merge()` moves the contents of every
+/// shard into the global index and returns, for each shard, the translation
+/// from provisional ids to global ids. Global ids are assigned
+/// deterministically, regardless of thread scheduling: new values are added in
+/// shard order, and in order of first occurrence within each shard.
+///
+/// # Example
+///
+/// ```
+/// extern crate crossbeam;
+/// # extern crate symbol_map;
+/// use symbol_map::indexing::{Indexing, ShardedIndexing};
+///
+/// # fn main() {
+/// let documents = ["the cat saw the dog", "a dog saw a cat"];
+/// let mut index = ShardedIndexing::<&str, usize>::with_shards(documents.len());
+/// let provisional: Vec<Vec<usize>> = crossbeam::scope(|scope| {
+///     let handles: Vec<_> = index.shards_mut().iter_mut().zip(documents.iter())
+///         .map(|(shard, document)| scope.spawn(move || {
+///             shard.encode_or_insert(document.split(' '))
+///         }))
+///         .collect();
+///     handles.into_iter().map(|h| h.join()).collect()
+/// });
+/// let translations = index.merge();
+/// let ids: Vec<usize> = provisional[1].iter().map(|&id| translations[1][id].unwrap()).collect();
+/// assert_eq!(ids, vec![4, 3, 2, 4, 1]);
+/// assert_eq!(index.global().get_symbol_id("a"), Some(4));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShardedIndexing<T, D, S = RandomState> where T: Eq + Hash, D: SymbolId {
+    global: HashIndexing<T, D, S>,
+    shards: Vec<HashIndexing<T, D, S>>,
+}
+
+impl<T, D, S> ShardedIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
+    /// Creates an index with an empty global index and `shards` empty local
+    /// shards.
+    pub fn with_shards(shards: usize) -> Self {
+        ShardedIndexing::with_global(HashIndexing::default(), shards)
+    }
+
+    /// Creates an index that merges into `global`, which may already hold
+    /// symbols (such as reserved ones), with `shards` empty local shards.
+    pub fn with_global(global: HashIndexing<T, D, S>, shards: usize) -> Self {
+        ShardedIndexing {
+            global,
+            shards: (0..shards).map(|_| HashIndexing::default()).collect(),
+        }
+    }
+
+    /// Returns the global index, which holds every symbol merged so far.
+    pub fn global(&self) -> &HashIndexing<T, D, S> {
+        &self.global
+    }
+
+    /// Returns the local shards, which may be handed out to separate threads.
+    /// Ids assigned by a shard are provisional until `merge()` is called.
+    pub fn shards_mut(&mut self) -> &mut [HashIndexing<T, D, S>] {
+        &mut self.shards
+    }
+
+    /// Moves the symbols in every local shard into the global index, leaving
+    /// the shards empty, so they assign provisional ids from 0 again.
+    ///
+    /// Returns one vector for each shard, in shard order, that maps each
+    /// provisional id in that shard (as a `usize` index) to its global id, as
+    /// `HashIndexing::merge()` does.
+    /// Values are moved rather than cloned, and values already present in the
+    /// global index are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the global index does not have enough ids left for every new
+    /// value in the shards. This is checked before any shard is merged, so the
+    /// global index and the shards are left unchanged.
+    pub fn merge(&mut self) -> Vec<Vec<Option<D>>> {
+        let global = &mut self.global;
+        global.check_ids_left(self.shards.iter().flat_map(|shard| {
+            shard.table.iter()
+                .filter(move |symbol| !shard.is_tombstone(symbol.id()))
+                .map(Symbol::data)
+        }));
+        self.shards.iter_mut().map(|shard| global.merge(mem::take(shard))).collect()
+    }
+
+    /// Merges any symbols left in the local shards, as `merge()` does, and
+    /// returns the global index.
+    pub fn into_global(mut self) -> HashIndexing<T, D, S> {
+        self.merge();
+        self.global
+    }
+}
+
+/// HashMap-backed table indexing that counts how many times each symbol has
+/// been looked up with `get_or_insert()`.
+///
+/// This is intended for building vocabularies: count every occurrence in a
+/// corpus, then use `prune_and_sort_by_frequency()` to drop rare symbols and
+/// give the most frequent ones the smallest ids.
+///
+/// # Example
+///
+/// ```
+/// use symbol_map::indexing::{CountingIndexing, Indexing};
+///
+/// let mut index = CountingIndexing::<&str, usize>::default();
+/// for word in "the cat saw the dog and the cat".split(' ') {
+///     index.get_or_insert(word);
+/// }
+/// index.prune_and_sort_by_frequency(2, None);
+/// assert_eq!(index.table().len(), 2);
+/// assert_eq!(*index.get(&"the").unwrap().id(), 0);
+/// assert_eq!(*index.get(&"cat").unwrap().id(), 1);
+/// assert_eq!(index.count(&0), Some(3));
+/// assert_eq!(index.count(&1), Some(2));
+/// assert!(index.get(&"dog").is_none());
+/// ```
+#[derive(Debug)]
+pub struct CountingIndexing<T, D, S = RandomState> where T: Eq + Hash, D: SymbolId {
+    index: HashIndexing<T, D, S>,
+    counts: Vec<usize>,
+}
+
+impl<T, D, S> CountingIndexing<T, D, S> where T: Eq + Hash, D: SymbolId, S: BuildHasher {
+    /// Creates a new, empty index that hashes values with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        CountingIndexing {
+            index: HashIndexing::with_hasher(hasher),
+            counts: Vec::new(),
+        }
+    }
+}
+
+impl<T, D, S> CountingIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
+    /// Returns the number of times the symbol with id `id` has been looked up
+    /// with `get_or_insert()`, or `None` if there is no such symbol. Symbols
+    /// that were indexed by `from_table()` start with a count of 0.
+    pub fn count(&self, id: &D) -> Option<usize> {
+        self.index.get_symbol(id).map(|_| self.counts[id.as_usize()])
+    }
+
+    /// Drops every symbol that occurs fewer than `min_count` times and, if
+    /// `max_size` is given, all but the `max_size` most frequent symbols that
+    /// remain. The remaining symbols are renumbered densely in order of
+    /// decreasing count, with ties broken by their original ids.
+    ///
+    /// Returns a vector that maps each old id (as a `usize` index) to the new id
+    /// of its symbol, or `None` if the symbol was dropped.
+    pub fn prune_and_sort_by_frequency(&mut self, min_count: usize, max_size: Option<usize>)
+                                       -> Vec<Option<D>> {
+        let mut by_frequency: Vec<usize> = self.index.table().iter().rev()
+            .map(|symbol| symbol.id().as_usize())
+            .filter(|&i| self.counts[i] >= min_count)
+            .collect();
+        // Stable, so ties keep their original relative order.
+        by_frequency.sort_by(|&a, &b| self.counts[b].cmp(&self.counts[a]));
+        if let Some(max_size) = max_size {
+            by_frequency.truncate(max_size);
+        }
+
+        let mut translation = vec![None; self.counts.len()];
+        let mut new_counts = Vec::with_capacity(by_frequency.len());
+        let mut next_id = Some(D::default());
+        for old_id in by_frequency {
+            translation[old_id] = next_id;
+            new_counts.push(self.counts[old_id]);
+            next_id = next_id.and_then(|id| id.checked_next());
+        }
+        self.index.remap(|symbol| translation[symbol.id().as_usize()]);
+        self.counts = new_counts;
+        translation
+    }
+
+    /// Rebuilds `self.counts` after the underlying table has been remapped,
+    /// given `(new id, count)` pairs for the retained symbols. The ids may
+    /// have gaps, so `self.counts` covers the largest of them.
+    fn set_counts(&mut self, moved: Vec<(D, usize)>) {
+        self.counts = vec![0; self.index.table().id_bound()];
+        for (id, count) in moved {
+            self.counts[id.as_usize()] = count;
+        }
+    }
+}
+
+impl<T, D, S> Default for CountingIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
+    fn default() -> Self {
+        CountingIndexing::with_hasher(S::default())
+    }
+}
+
+impl<T, D, S> Indexing for CountingIndexing<T, D, S>
+    where T: Eq + Hash, D: SymbolId, S: BuildHasher + Default {
+    type Data = T;
+    type Id = D;
+
+    fn from_table(table: Table<T, D>) -> Self {
+        CountingIndexing {
+            counts: vec![0; table.id_bound()],
+            index: HashIndexing::from_table(table),
+        }
+    }
+
+    fn table(&self) -> &Table<Self::Data, Self::Id> { self.index.table() }
+
+    fn to_table(self) -> Table<Self::Data, Self::Id> { self.index.to_table() }
+
+    fn get<Q>(&self, data: &Q) -> Option<&Symbol<T, D>> where T: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.index.get(data)
+    }
+
+    fn get_or_insert(&mut self, data: T) -> Insertion<&Symbol<T, D>> {
+        match self.try_get_or_insert(data) {
+            Ok(insertion) => insertion,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_get_or_insert(&mut self, data: T)
+                         -> Result<Insertion<&Symbol<T, D>>, CapacityError<T>> {
+        let insertion = self.index.try_get_or_insert(data)?;
+        let id = insertion.map(|symbol| symbol.id().as_usize()).unwrap();
+        // Ids follow the largest one in the table, which may leave a gap.
+        if id >= self.counts.len() {
+            self.counts.resize(id + 1, 0);
+        }
+        self.counts[id] += 1;
+        Ok(insertion)
+    }
+
+    fn get_symbol<'s>(&'s self, id: &D) -> Option<&'s Symbol<T, D>> {
+        self.index.get_symbol(id)
+    }
+
+    fn retain<F>(&mut self, f: F) -> Vec<Option<D>> where F: FnMut(&Symbol<T, D>) -> bool {
+        let translation = self.index.retain(f);
+        let moved = translation.iter().zip(self.counts.iter())
+            .filter_map(|(new_id, &count)| new_id.map(|id| (id, count)))
+            .collect();
+        self.set_counts(moved);
+        translation
+    }
+
+    fn remap<F>(&mut self, mut f: F) where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let mut moved = Vec::with_capacity(self.counts.len());
+        {
+            let counts = &self.counts;
+            self.index.remap(|symbol| {
+                let new_id = f(symbol);
+                if let Some(id) = new_id {
+                    moved.push((id, counts[symbol.id().as_usize()]));
+                }
+                new_id
+            });
+        }
+        self.set_counts(moved);
+    }
+
+    fn try_remap<F>(&mut self, mut f: F) -> Result<(), RemapError<D>>
+        where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let mut moved = Vec::with_capacity(self.counts.len());
+        {
+            let counts = &self.counts;
+            self.index.try_remap(|symbol| {
+                let new_id = f(symbol);
+                if let Some(id) = new_id {
+                    moved.push((id, counts[symbol.id().as_usize()]));
+                }
+                new_id
+            })?;
+        }
+        self.set_counts(moved);
+        Ok(())
+    }
+}
+
+/// A read-only view of an `Indexing` that maps values without a symbol to a
+/// designated fallback symbol, such as an "unknown word" token.
+///
+/// A `FrozenIndexing` never inserts new symbols. It counts how many lookups
+/// went through `get_or_fallback()` and `encode_or_unk()`, and how many of
+/// those fell back, which is useful for measuring out-of-vocabulary rates.
+///
+/// # Example
+///
+/// ```
+/// use symbol_map::indexing::{FrozenIndexing, HashIndexing, Indexing};
+///
+/// let mut index = HashIndexing::<&str, usize>::default();
+/// index.encode_or_insert(vec!["the", "cat", "saw"]);
+/// let (frozen, translation) = FrozenIndexing::new(index, "<unk>").unwrap();
+/// assert_eq!(translation, vec![1, 2, 3]);
+/// assert_eq!(frozen.encode_or_unk(&["the", "dog", "saw"]), vec![1, 0, 3]);
+/// assert_eq!(frozen.lookups(), 3);
+/// assert_eq!(frozen.fallbacks(), 1);
+/// ```
+#[derive(Debug)]
+pub struct FrozenIndexing<I> where I: Indexing {
+    index: I,
+    fallback: I::Id,
+    lookups: AtomicUsize,
+    fallbacks: AtomicUsize,
+}
+
+impl<I> FrozenIndexing<I> where I: Indexing {
+    /// Freezes `index`, using `fallback` as the fallback symbol and giving it
+    /// id 0.
+    ///
+    /// If `fallback` is not already the symbol with id 0, it is inserted (if
+    /// necessary) and moved to id 0, and the ids of the symbols that preceded
+    /// it are shifted up by one. Returns the frozen index together with a
+    /// vector that maps each old id (as a `usize` index) to the new id of its
+    /// symbol, so that ids stored elsewhere can be updated. To keep every id
+    /// stable, insert the fallback value into `index` before any other value,
+    /// or use `with_fallback_id()`.
+    ///
+    /// Returns `index` back unchanged if its symbols cannot be renumbered
+    /// because `try_remap()` rejects the new ids, for example because some of
+    /// them are reserved. If `fallback` was inserted, it is removed again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fallback` must be inserted but every value of `I::Id` has
+    /// already been assigned.
+    pub fn new(mut index: I, fallback: I::Data) -> Result<(Self, Vec<I::Id>), I> {
+        let len = index.table().len();
+        let (fallback_id, inserted) = match index.get_or_insert(fallback) {
+            Insertion::Present(symbol) => (*symbol.id(), false),
+            Insertion::New(symbol) => (*symbol.id(), true),
+        };
+        let first_id = I::Id::default();
+        let new_id = |id: &I::Id| match id.cmp(&fallback_id) {
+            Ordering::Less => id.next(),
+            Ordering::Equal => first_id,
+            Ordering::Greater => *id,
+        };
+        let mut translation: Vec<I::Id> = index.table().iter().rev()
+            .map(|symbol| new_id(symbol.id()))
+            .collect();
+        translation.truncate(len);
+        if fallback_id != first_id && index.try_remap(|symbol| Some(new_id(symbol.id()))).is_err() {
+            if inserted {
+                // Every other symbol keeps its id, so this cannot fail.
+                index.remap(|symbol| Some(*symbol.id()).filter(|id| *id != fallback_id));
+            }
+            return Err(index)
+        }
+        Ok((FrozenIndexing::from_parts(index, first_id), translation))
+    }
+
+    /// Freezes `index`, using its existing symbol with id `id` as the fallback
+    /// symbol. No ids are changed.
+    ///
+    /// Returns `index` back if it has no symbol with id `id`.
+    pub fn with_fallback_id(index: I, id: I::Id) -> Result<Self, I> {
+        if index.get_symbol(&id).is_some() {
+            Ok(FrozenIndexing::from_parts(index, id))
+        } else {
+            Err(index)
+        }
+    }
+
+    fn from_parts(index: I, fallback: I::Id) -> Self {
+        FrozenIndexing {
+            index,
+            fallback,
+            lookups: AtomicUsize::new(0),
+            fallbacks: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a read-only view of the underlying index.
+    pub fn index(&self) -> &I {
+        &self.index
+    }
+
+    /// Unfreezes the underlying index, discarding the lookup counters.
+    pub fn into_inner(self) -> I {
+        self.index
+    }
+
+    /// Returns the fallback symbol.
+    pub fn fallback(&self) -> &Symbol<I::Data, I::Id> {
+        // The index cannot be modified while it is frozen, so the fallback
+        // symbol checked for at construction is still present.
+        self.index.get_symbol(&self.fallback).unwrap()
+    }
+
+    /// Looks up `data` in the index without counting the lookup or falling
+    /// back. Returns `Some(&symbol)` if a symbol is present, else `None`.
+    pub fn get<Q>(&self, data: &Q) -> Option<&Symbol<I::Data, I::Id>>
+        where I::Data: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.index.get(data)
+    }
+
+    /// Looks up the symbol with id `id` in the index. Returns `Some(symbol)`
+    /// if a symbol is present, else `None`.
+    pub fn get_symbol(&self, id: &I::Id) -> Option<&Symbol<I::Data, I::Id>> {
+        self.index.get_symbol(id)
+    }
+
+    /// Looks up `data` in the index, returning the fallback symbol if it has no
+    /// symbol of its own.
+    pub fn get_or_fallback<Q>(&self, data: &Q) -> &Symbol<I::Data, I::Id>
+        where I::Data: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.lookups.fetch_add(1, AtomicOrdering::Relaxed);
+        match self.index.get(data) {
+            Some(symbol) => symbol,
+            None => {
+                self.fallbacks.fetch_add(1, AtomicOrdering::Relaxed);
+                self.fallback()
+            },
+        }
+    }
+
+    /// Looks up each value in `data`, as `get_or_fallback()` does, and returns
+    /// the ids of their symbols in order.
+    pub fn encode_or_unk<'q, It, Q>(&self, data: It) -> Vec<I::Id>
+        where It: IntoIterator<Item = &'q Q>, I::Data: Borrow<Q>, Q: 'q + ?Sized + Eq + Hash {
+        data.into_iter().map(|value| *self.get_or_fallback(value).id()).collect()
+    }
+
+    /// Returns the number of lookups made with `get_or_fallback()` and
+    /// `encode_or_unk()`.
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Returns the number of lookups that returned the fallback symbol because
+    /// the value looked up had no symbol.
+    pub fn fallbacks(&self) -> usize {
+        self.fallbacks.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Resets the lookup and fallback counters to 0.
+    pub fn reset_counters(&self) {
+        self.lookups.store(0, AtomicOrdering::Relaxed);
+        self.fallbacks.store(0, AtomicOrdering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::clone_on_copy, clippy::ptr_eq, clippy::redundant_static_lifetimes)]
+mod test {
+    use super::{AlignPolicy, ConcurrentHashIndexing, CountingIndexing, Entry, FrozenIndexing,
+                HashIndexing, Indexing, Insertion, Ref, ShardedIndexing, UnknownId, UnknownSymbol};
+    use ::{SymbolId, Table};
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+    use std::cmp::Ordering;
+    use std::hash::{Hash, Hasher};
+    use std::str::FromStr;
+
+    const VALUES: &'static [usize] = &[101, 203, 500, 30, 0, 1];
+
+    #[test]
+    fn ref_impls_ok() {
+        let x1 = String::from_str("foo").unwrap();
+        let x2 = String::from_str("foo").unwrap();
+        let x3 = String::from_str("fo").unwrap();
+        let x4 = String::from_str("fox").unwrap();
+        assert!(x1 == x2);
+        assert!(&x1 as *const String != &x2 as *const String);
+
+        let r1 = Ref::new(&x1);
+        let r2 = Ref::new(&x2);
+        let r3 = Ref::new(&x3);
+        let r4 = Ref::new(&x4);
+        // Eq, PartialEq.
+        assert_eq!(r1, r2);
+        assert!(r1 != r3);
+        assert!(r1 != r4);
+
+        // Ord (skip PartialOrd).
+        assert_eq!(r1.cmp(&r2), Ordering::Equal);
+        assert_eq!(r1.cmp(&r3), Ordering::Greater);
+        assert_eq!(r1.cmp(&r4), Ordering::Less);
+
+        // Hash.
+        let mut rh1 = DefaultHasher::new();
+        let mut rh2 = DefaultHasher::new();
+        let mut rh3 = DefaultHasher::new();
+        let mut rh4 = DefaultHasher::new();
+        r1.hash(&mut rh1);
+        r2.hash(&mut rh2);
+        r3.hash(&mut rh3);
+        r4.hash(&mut rh4);
+        let rh1 = rh1.finish();
+        let rh2 = rh2.finish();
+        let rh3 = rh3.finish();
+        let rh4 = rh4.finish();
+
+        let mut xh1 = DefaultHasher::new();
+        let mut xh2 = DefaultHasher::new();
+        let mut xh3 = DefaultHasher::new();
+        let mut xh4 = DefaultHasher::new();
+        x1.hash(&mut xh1);
+        x2.hash(&mut xh2);
+        x3.hash(&mut xh3);
+        x4.hash(&mut xh4);
+        let xh1 = xh1.finish();
+        let xh2 = xh2.finish();
+        let xh3 = xh3.finish();
+        let xh4 = xh4.finish();
+
+        assert_eq!(rh1, xh1);
+        assert_eq!(rh2, xh2);
+        assert_eq!(rh3, xh3);
+        assert_eq!(rh4, xh4);
+    }
+
+    #[test]
+    fn hash_indexing_empty_ok() {
+        let t = Table::<usize, usize>::new();
+        assert_eq!(t.len(), 0);
+        let i = HashIndexing::<usize, usize>::from_table(t);
+        assert!(i.by_symbol.is_empty());
+        assert!(i.table.is_empty());
+    }
+
+    #[test]
+    fn hash_indexing_from_table_ok() {
+        let mut t = Table::<usize, usize>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let expected_len = t.len();
+        let expected_values: Vec<(usize, usize)> =
+            t.iter().map(|s| (*s.data(), *s.id())).collect();
+
+        let i = HashIndexing::<usize, usize>::from_table(t);
+        assert_eq!(i.by_symbol.len(), expected_len);
+        assert_eq!(i.table.len(), expected_len);
+        for (data, id) in expected_values.into_iter() {
+            let data_ref = Ref::new(&data);
+            unsafe {
+                assert_eq!(i.by_symbol.get(&data_ref).unwrap().deref().data(), &data);
+                assert_eq!(i.by_symbol.get(&data_ref).unwrap().deref().id(), &id);
+                assert_eq!(i.table.get(&id).unwrap().data(), &data);
+            }
+        }
+    }
+
+    #[test]
+    fn hash_indexing_empty_insertion_ok() {
+        let mut i = HashIndexing::<usize, usize>::default();
+
+        for v in VALUES.iter() {
+            assert!(i.get(v).is_none());
+            let id = match i.get_or_insert(*v) {
+                Insertion::Present(_) => panic!(),
+                Insertion::New(symbol) => {
+                    assert_eq!(symbol.data(), v);
+                    *symbol.id()
+                },
+            };
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+    }
+
+    
+    #[test]
+    fn indexed_present_ok() {
+        let mut t = Table::<usize, usize>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+
+        let mut i = HashIndexing::<usize, usize>::from_table(t);
+        for v in VALUES.iter() {
+            assert_eq!(i.get(v).unwrap().data(), v);
+            let id = match i.get_or_insert(*v) {
+                Insertion::New(_) => panic!(),
+                Insertion::Present(symbol) => {
+                    assert_eq!(symbol.data(), v);
+                    *symbol.id()
+                },
+            };
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn try_get_or_insert_exhausted_ok() {
+        let mut i = HashIndexing::<usize, u8>::default();
+        for v in 0..256 {
+            match i.try_get_or_insert(v) {
+                Ok(Insertion::New(symbol)) => assert_eq!(symbol.id().as_usize(), v),
+                _ => panic!(),
+            }
+        }
+        assert_eq!(i.try_get_or_insert(256).unwrap_err().into_value(), 256);
+        assert!(i.get(&256).is_none());
+        match i.try_get_or_insert(255) {
+            Ok(Insertion::Present(symbol)) => assert_eq!(*symbol.id(), 255),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn hash_indexing_retain_ok() {
+        let mut i = HashIndexing::<usize, usize>::default();
+        for v in VALUES.iter() {
+            i.get_or_insert(*v);
+        }
+        let translation = i.retain(|symbol| *symbol.data() >= 100);
+        assert_eq!(translation, vec![Some(0), Some(1), Some(2), None, None, None]);
+        assert_eq!(i.table().len(), 3);
+        assert_eq!(i.by_symbol.len(), 3);
+        for (old_id, v) in VALUES.iter().enumerate() {
+            match translation[old_id] {
+                Some(new_id) => {
+                    assert_eq!(i.get(v).unwrap().id(), &new_id);
+                    assert_eq!(i.get_symbol(&new_id).unwrap().data(), v);
+                },
+                None => assert!(i.get(v).is_none()),
+            }
+        }
+        match i.get_or_insert(VALUES[5]) {
+            Insertion::New(symbol) => assert_eq!(*symbol.id(), 3),
+            Insertion::Present(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn hash_indexing_remap_ok() {
+        let mut i = HashIndexing::<usize, usize>::default();
+        for v in VALUES.iter() {
+            i.get_or_insert(*v);
+        }
+        let last = VALUES.len() - 1;
+        i.remap(|symbol| Some(last - *symbol.id()));
+        for (old_id, v) in VALUES.iter().enumerate() {
+            assert_eq!(*i.get(v).unwrap().id(), last - old_id);
+            assert_eq!(i.get_symbol(&(last - old_id)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn hash_indexing_try_remap_err_ok() {
+        let mut i = HashIndexing::<usize, usize>::default();
+        for v in VALUES.iter() {
+            i.get_or_insert(*v);
+        }
+        let err = i.try_remap(|_| Some(0)).unwrap_err();
+        assert_eq!(err.duplicates(), &[0]);
+        assert_eq!(i.by_symbol.len(), VALUES.len());
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(*i.get(v).unwrap().id(), id);
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn hash_indexing_panic_in_closure_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut i = HashIndexing::<usize, usize>::default();
+        for v in VALUES.iter() {
+            i.get_or_insert(*v);
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.retain(|_| panic!())));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.remap(|_| panic!())));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.try_remap(|_| panic!())));
+        assert!(result.is_err());
+        // A duplicate id is rejected by the table before anything is modified.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.remap(|_| Some(0))));
+        assert!(result.is_err());
+        assert_eq!(i.table().len(), VALUES.len());
+        assert_eq!(i.by_symbol.len(), VALUES.len());
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(*i.get(v).unwrap().id(), id);
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn hash_indexing_retain_hashes_dropped_only_ok() {
+        use std::cell::Cell;
+        use std::hash::BuildHasher;
+
+        thread_local!(static HASHES: Cell<usize> = const { Cell::new(0) });
+
+        /// Counts the values hashed on this thread.
+        #[derive(Clone, Default)]
+        struct CountingState;
+
+        impl BuildHasher for CountingState {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                HASHES.with(|hashes| hashes.set(hashes.get() + 1));
+                DefaultHasher::new()
+            }
+        }
+
+        let mut i = HashIndexing::<usize, usize, CountingState>::default();
+        i.encode_or_insert(0..100);
+        HASHES.with(|hashes| hashes.set(0));
+        i.retain(|s| *s.data() % 2 == 0);
+        assert_eq!(HASHES.with(Cell::get), 50);
+        i.remap(|s| if *s.id() < 10 { Some(49 - *s.id()) } else { Some(*s.id() - 10) });
+        i.try_remap(|s| if *s.id() < 10 { None } else { Some(*s.id() - 10) }).unwrap();
+        assert_eq!(HASHES.with(Cell::get), 60);
+        assert_eq!(i.by_symbol.len(), 40);
+        for symbol in i.table().iter() {
+            assert_eq!(i.get(symbol.data()).unwrap().id(), symbol.id());
+        }
+        // The second remapping dropped the symbols given ids 0..10 by the first.
+        for v in (0..100).filter(|v| v % 2 == 0) {
+            assert_eq!(i.get(&v).is_none(), (20..40).contains(&v));
+        }
+    }
+
+    #[test]
+    fn counting_indexing_count_ok() {
+        let mut i = CountingIndexing::<usize, usize>::default();
+        for v in [5, 3, 5, 7, 5, 3].iter() {
+            i.get_or_insert(*v);
+        }
+        assert_eq!(i.count(&0), Some(3));
+        assert_eq!(i.count(&1), Some(2));
+        assert_eq!(i.count(&2), Some(1));
+        assert_eq!(i.count(&3), None);
+        // Lookups without insertion are not counted.
+        assert_eq!(*i.get(&5).unwrap().id(), 0);
+        assert_eq!(i.count(&0), Some(3));
+    }
+
+    #[test]
+    fn counting_indexing_from_table_ok() {
+        let mut t = Table::<usize, usize>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let mut i = CountingIndexing::<usize, usize>::from_table(t);
+        for id in 0..VALUES.len() {
+            assert_eq!(i.count(&id), Some(0));
+        }
+        i.get_or_insert(VALUES[2]);
+        assert_eq!(i.count(&2), Some(1));
+    }
+
+    #[test]
+    fn counting_indexing_prune_and_sort_ok() {
+        let mut i = CountingIndexing::<usize, u8>::default();
+        for v in [1, 2, 3, 2, 4, 3, 2, 4, 5, 5].iter() {
+            i.get_or_insert(*v);
+        }
+        let translation = i.prune_and_sort_by_frequency(2, None);
+        assert_eq!(translation, vec![None, Some(0), Some(1), Some(2), Some(3)]);
+        assert_eq!(i.table().len(), 4);
+        assert!(i.get(&1).is_none());
+        for (v, id, count) in [(2, 0, 3), (3, 1, 2), (4, 2, 2), (5, 3, 2)].iter() {
+            assert_eq!(i.get(v).unwrap().id(), id);
+            assert_eq!(i.get_symbol(id).unwrap().data(), v);
+            assert_eq!(i.count(id), Some(*count));
+        }
+
+        let translation = i.prune_and_sort_by_frequency(0, Some(2));
+        assert_eq!(translation, vec![Some(0), Some(1), None, None]);
+        assert_eq!(i.table().len(), 2);
+        assert_eq!(i.count(&1), Some(2));
+        assert!(i.get(&4).is_none());
+    }
+
+    #[test]
+    fn counting_indexing_retain_ok() {
+        let mut i = CountingIndexing::<usize, usize>::default();
+        for v in [1, 2, 3, 3].iter() {
+            i.get_or_insert(*v);
+        }
+        i.retain(|symbol| *symbol.data() != 2);
+        assert_eq!(i.count(&0), Some(1));
+        assert_eq!(i.count(&1), Some(2));
+        assert_eq!(i.count(&2), None);
+
+        i.remap(|symbol| Some(1 - *symbol.id()));
+        assert_eq!(*i.get(&3).unwrap().id(), 0);
+        assert_eq!(i.count(&0), Some(2));
+        assert_eq!(i.count(&1), Some(1));
+
+        assert!(i.try_remap(|_| Some(5)).is_err());
+        assert_eq!(i.count(&0), Some(2));
+        assert_eq!(i.count(&1), Some(1));
+    }
+
+    #[test]
+    fn counting_indexing_sparse_ids_ok() {
+        let mut i = CountingIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["a", "b", "c", "b"]);
+        i.remap(|symbol| Some(*symbol.id() * 2));
+        assert_eq!(i.count(&2), Some(2));
+        assert_eq!(i.count(&4), Some(1));
+        assert_eq!(i.count(&1), None);
+        assert_eq!(i.get_or_insert("d").map(|s| *s.id()), Insertion::New(5));
+        assert_eq!(i.count(&5), Some(1));
+        assert_eq!(i.get_or_insert("c").map(|s| *s.id()), Insertion::Present(4));
+        assert_eq!(i.count(&4), Some(2));
+
+        assert_eq!(i.prune_and_sort_by_frequency(0, None),
+                   vec![Some(2), None, Some(0), None, Some(1), Some(3)]);
+        assert_eq!(i.count(&0), Some(2));
+        assert_eq!(i.count(&3), Some(1));
+
+        let mut t = Table::<&str, usize>::new();
+        t.insert_all(vec!["a", "b"]);
+        t.remap(|symbol| Some(*symbol.id() + 3));
+        let mut i = CountingIndexing::<&str, usize>::from_table(t);
+        assert_eq!(i.count(&4), Some(0));
+        i.get_or_insert("b");
+        assert_eq!(i.count(&4), Some(1));
+    }
+
+    #[test]
+    fn hash_indexing_get_borrowed_ok() {
+        let mut i = HashIndexing::<String, usize>::default();
+        for v in ["NNP", "VBD", "DT"].iter() {
+            i.get_or_insert(v.to_string());
+        }
+        assert_eq!(i.get("VBD").unwrap().data(), "VBD");
+        assert_eq!(*i.get("VBD").unwrap().id(), 1);
+        assert_eq!(i.get(&"DT".to_string()).unwrap().data(), "DT");
+        assert!(i.get("NN").is_none());
+        assert_eq!(i.get_symbol_id("NNP"), Some(0));
+        assert_eq!(i.get_symbol_id("DT"), Some(2));
+        assert_eq!(i.get_symbol_id("NN"), None);
+
+        let mut i = CountingIndexing::<Vec<u8>, u8>::default();
+        i.get_or_insert(b"abc".to_vec());
+        assert_eq!(i.get_symbol_id(&b"abc"[..]), Some(0));
+        assert!(i.get(&b"ab"[..]).is_none());
+    }
+
+    #[test]
+    fn hash_indexing_get_or_insert_borrowed_ok() {
+        let mut i = HashIndexing::<String, usize>::default();
+        match i.get_or_insert_borrowed("NNP") {
+            Insertion::New(symbol) => assert_eq!((symbol.data().as_str(), *symbol.id()), ("NNP", 0)),
+            Insertion::Present(_) => panic!(),
+        }
+        match i.get_or_insert_borrowed("NNP") {
+            Insertion::Present(symbol) => assert_eq!(*symbol.id(), 0),
+            Insertion::New(_) => panic!(),
+        }
+        match i.get_or_insert_borrowed("VBD") {
+            Insertion::New(symbol) => assert_eq!(*symbol.id(), 1),
+            Insertion::Present(_) => panic!(),
+        }
+        assert_eq!(i.get_symbol_id("VBD"), Some(1));
+    }
+
+    #[test]
+    fn hash_indexing_get_or_insert_with_ok() {
+        let mut i = HashIndexing::<String, u8>::default();
+        let mut calls = 0;
+        for v in ["a", "b", "a", "a", "b"].iter() {
+            i.get_or_insert_with(*v, || {
+                calls += 1;
+                v.to_string()
+            });
+        }
+        assert_eq!(calls, 2);
+        assert_eq!(i.table().len(), 2);
+
+        for v in 2..256 {
+            i.get_or_insert(v.to_string());
+        }
+        let err = i.try_get_or_insert_with("256", || "256".to_string()).unwrap_err();
+        assert_eq!(err.into_value(), "256");
+        match i.try_get_or_insert_with("a", || panic!()) {
+            Ok(Insertion::Present(symbol)) => assert_eq!(*symbol.id(), 0),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn hash_indexing_entry_ok() {
+        let mut i = HashIndexing::<String, usize>::default();
+        match i.entry("NNP") {
+            Entry::Occupied(_) => panic!(),
+            Entry::Vacant(e) => {
+                assert_eq!(e.key(), "NNP");
+                let symbol = e.insert("NNP".to_string());
+                assert_eq!(*symbol.id(), 0);
+            },
+        }
+        match i.entry("NNP") {
+            Entry::Occupied(e) => {
+                assert_eq!(e.key(), "NNP");
+                assert_eq!(*e.get().id(), 0);
+            },
+            Entry::Vacant(_) => panic!(),
+        }
+        assert_eq!(*i.entry("VBD").or_insert().id(), 1);
+        assert_eq!(*i.entry("VBD").or_insert_with(|| panic!()).id(), 1);
+        assert_eq!(*i.entry("DT").or_insert_with(|| "DT".to_string()).id(), 2);
+
+        let mut seen = None;
+        i.entry("VBD").and_modify(|symbol| seen = Some(*symbol.id()));
+        assert_eq!(seen, Some(1));
+        let mut seen = None;
+        assert_eq!(i.entry("NN").and_modify(|symbol| seen = Some(*symbol.id())).key(), "NN");
+        assert_eq!(seen, None);
+
+        assert_eq!(i.table().len(), 3);
+        for (id, v) in ["NNP", "VBD", "DT"].iter().enumerate() {
+            assert_eq!(i.get_symbol_id(*v), Some(id));
+        }
+    }
+
+    #[test]
+    fn hash_indexing_entry_exhausted_ok() {
+        let mut i = HashIndexing::<usize, u8>::default();
+        for v in 0..256 {
+            i.entry(&v).or_insert();
+        }
+        match i.entry(&256) {
+            Entry::Vacant(e) => assert_eq!(e.try_insert(256).unwrap_err().into_value(), 256),
+            Entry::Occupied(_) => panic!(),
+        }
+        assert_eq!(i.entry(&256).or_try_insert_with(|| 256).unwrap_err().into_value(), 256);
+        assert_eq!(*i.entry(&255).or_try_insert_with(|| panic!()).unwrap().id(), 255);
+        assert_eq!(i.table().len(), 256);
+    }
+
+    #[test]
+    fn hash_indexing_get_or_insert_all_ok() {
+        let mut i = HashIndexing::<&str, usize>::default();
+        let e = i.get_or_insert_all(vec!["NNP", "VBD", "NNP", "DT"]);
+        assert_eq!(e.len(), 3);
+        assert_eq!(e.first(), Some(&0));
+        assert_eq!(e.last(), Some(&2));
+        let e = i.get_or_insert_all(vec!["DT", "NN", "VBD", "IN", "NN"]);
+        assert_eq!(e.len(), 2);
+        assert_eq!(e.first(), Some(&3));
+        assert_eq!(e.last(), Some(&4));
+        let e = i.get_or_insert_all(vec!["NNP"]);
+        assert!(e.is_empty());
+        assert_eq!(i.table().len(), 5);
+        assert_eq!(i.get_symbol_id("IN"), Some(4));
+    }
+
+    #[test]
+    fn hash_indexing_from_iter_ok() {
+        let mut i: HashIndexing<usize, usize> =
+            VALUES.iter().chain(VALUES.iter()).cloned().collect();
+        assert_eq!(i.table().len(), VALUES.len());
+        i.extend(vec![VALUES[0], 1000]);
+        assert_eq!(i.table().len(), VALUES.len() + 1);
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+        }
+        assert_eq!(i.get_symbol_id(&1000), Some(VALUES.len()));
+    }
+
+    #[test]
+    fn encode_decode_ok() {
+        let mut i = HashIndexing::<String, u16>::default();
+        let tokens = ["the", "dog", "saw", "the", "cat"];
+        let ids = i.encode_or_insert(tokens.iter().map(|t| t.to_string()));
+        assert_eq!(ids, vec![0, 1, 2, 0, 3]);
+        assert_eq!(i.table().len(), 4);
+        assert_eq!(i.encode(tokens.iter().cloned()), Ok(ids.clone()));
+        assert_eq!(i.encode(Vec::<&str>::new()), Ok(Vec::new()));
+        let decoded: Vec<&str> = i.decode(&ids).unwrap().into_iter().map(|t| t.as_str()).collect();
+        assert_eq!(decoded, tokens);
+        assert_eq!(i.decode(&[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn encode_unknown_symbol_err() {
+        let mut i = HashIndexing::<String, usize>::default();
+        i.encode_or_insert(vec!["a".to_string(), "b".to_string()]);
+        let e = i.encode(vec!["a", "c", "b", "d"]).unwrap_err();
+        assert_eq!(e, UnknownSymbol { position: 1, });
+        assert_eq!(e.position(), 1);
+        assert_eq!(e.to_string(), "no symbol for value at position 1");
+        assert_eq!(i.table().len(), 2);
+    }
+
+    #[test]
+    fn decode_unknown_id_err() {
+        let mut i = HashIndexing::<usize, u8>::default();
+        i.encode_or_insert(VALUES.iter().cloned());
+        let e = i.decode(&[0, 1, 200, 2, 201]).unwrap_err();
+        assert_eq!(e, UnknownId { position: 2, id: 200, });
+        assert_eq!(*e.id(), 200);
+        assert_eq!(e.to_string(), "no symbol for id 200 at position 2");
+    }
+
+    #[test]
+    fn counting_indexing_encode_or_insert_ok() {
+        let mut i = CountingIndexing::<&str, usize>::default();
+        assert_eq!(i.encode_or_insert(vec!["a", "b", "a"]), vec![0, 1, 0]);
+        assert_eq!(i.count(&0), Some(2));
+        assert_eq!(i.count(&1), Some(1));
+        assert_eq!(i.decode(&[1, 0]), Ok(vec![&"b", &"a"]));
+    }
+
+    #[test]
+    fn frozen_indexing_fallback_first_ok() {
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["<unk>", "a", "b"]);
+        let (f, translation) = FrozenIndexing::new(i, "<unk>").unwrap();
+        assert_eq!(translation, vec![0, 1, 2]);
+        assert_eq!(*f.fallback().id(), 0);
+        assert_eq!(f.encode_or_unk(&["b", "c", "a", "d"]), vec![2, 0, 1, 0]);
+        assert_eq!(f.lookups(), 4);
+        assert_eq!(f.fallbacks(), 2);
+        assert_eq!(f.get_or_fallback("<unk>").id(), &0);
+        assert_eq!(f.lookups(), 5);
+        assert_eq!(f.fallbacks(), 2);
+        assert!(f.get("c").is_none());
+        assert_eq!(f.lookups(), 5);
+        assert_eq!(f.index().table().len(), 3);
+        f.reset_counters();
+        assert_eq!(f.lookups(), 0);
+        assert_eq!(f.fallbacks(), 0);
+        assert_eq!(f.into_inner().table().len(), 3);
+    }
+
+    #[test]
+    fn frozen_indexing_fallback_moved_ok() {
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["a", "b", "<unk>", "c"]);
+        let (f, translation) = FrozenIndexing::new(i, "<unk>").unwrap();
+        assert_eq!(translation, vec![1, 2, 0, 3]);
+        assert_eq!(f.encode_or_unk(&["<unk>", "a", "b", "c"]), vec![0, 1, 2, 3]);
+
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["a", "b"]);
+        let (f, translation) = FrozenIndexing::new(i, "<unk>").unwrap();
+        assert_eq!(translation, vec![1, 2]);
+        assert_eq!(f.encode_or_unk(&["<unk>", "a", "b", "z"]), vec![0, 1, 2, 0]);
+        assert_eq!(f.get_symbol(&0).unwrap().data(), &"<unk>");
+        assert_eq!(f.fallbacks(), 1);
+    }
+
+    #[test]
+    fn frozen_indexing_with_fallback_id_ok() {
+        let mut i = HashIndexing::<&str, u8>::default();
+        i.encode_or_insert(vec!["a", "b", "<unk>"]);
+        let f = FrozenIndexing::with_fallback_id(i, 2).unwrap();
+        assert_eq!(f.fallback().data(), &"<unk>");
+        assert_eq!(f.encode_or_unk(&["a", "x"]), vec![0, 2]);
+        assert_eq!(f.fallbacks(), 1);
+
+        let i = FrozenIndexing::with_fallback_id(f.into_inner(), 3).unwrap_err();
+        assert_eq!(i.table().len(), 3);
+    }
+
+    #[test]
+    fn hash_indexing_with_reserved_ok() {
+        let mut i = HashIndexing::<String, u8>::with_reserved(
+            &["<pad>".to_string(), "<s>".to_string(), "<pad>".to_string(), "</s>".to_string()]);
+        assert_eq!(i.reserved, 3);
+        assert_eq!(i.get_symbol_id("<pad>"), Some(0));
+        assert_eq!(i.get_symbol_id("<s>"), Some(1));
+        assert_eq!(i.get_symbol_id("</s>"), Some(2));
+        assert!(i.is_reserved(&2));
+        assert!(!i.is_reserved(&3));
+        assert_eq!(i.encode_or_insert(vec!["a".to_string(), "<s>".to_string()]), vec![3, 1]);
+
+        let i = HashIndexing::<usize, usize>::with_reserved(&[]);
+        assert!(i.table().is_empty());
+        assert!(!i.is_reserved(&0));
+    }
+
+    #[test]
+    fn hash_indexing_retain_keeps_reserved_ok() {
+        let mut i = HashIndexing::<usize, usize>::with_reserved(&[1000, 1001]);
+        i.encode_or_insert(VALUES.iter().cloned());
+        let translation = i.retain(|symbol| *symbol.data() == VALUES[1]);
+        assert_eq!(translation[..3], [Some(0), Some(1), None]);
+        assert_eq!(translation[3], Some(2));
+        assert_eq!(i.table().len(), 3);
+        assert_eq!(i.get_symbol_id(&1000), Some(0));
+        assert_eq!(i.get_symbol_id(&1001), Some(1));
+        assert_eq!(i.get_symbol_id(&VALUES[1]), Some(2));
+    }
+
+    #[test]
+    fn hash_indexing_remap_keeps_reserved_ok() {
+        let mut i = HashIndexing::<usize, usize>::with_reserved(&[1000, 1001]);
+        i.encode_or_insert(vec![5, 6, 7]);
+        // Reverses the non-reserved symbols.
+        i.remap(|symbol| match *symbol.id() {
+            id if id < 2 => Some(id),
+            id => Some(6 - id),
+        });
+        assert_eq!(i.get_symbol_id(&1000), Some(0));
+        assert_eq!(i.get_symbol_id(&1001), Some(1));
+        assert_eq!(i.get_symbol_id(&5), Some(4));
+        assert_eq!(i.get_symbol_id(&7), Some(2));
+
+        // Dropping or renumbering a reserved symbol is rejected.
+        let err = i.try_remap(|symbol| match *symbol.id() {
+            0 => None,
+            id => Some(id - 1),
+        }).unwrap_err();
+        assert_eq!(err.reserved(), &[0, 1]);
+        assert_eq!(err.to_string(), "remapping would change reserved ids: [0, 1]");
+        let err = i.try_remap(|symbol| Some(*symbol.id() % 4)).unwrap_err();
+        assert!(err.reserved().is_empty());
+        assert_eq!(err.duplicates(), &[0]);
+        assert_eq!(i.get_symbol_id(&1001), Some(1));
+        assert!(i.try_remap(|symbol| Some(*symbol.id())).is_ok());
+    }
+
+    #[test]
+    fn hash_indexing_remap_reserved_panics_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut i = HashIndexing::<&str, usize>::with_reserved(&["<pad>", "<s>"]);
+        i.encode_or_insert(vec!["a", "b"]);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            i.remap(|symbol| Some(if *symbol.id() < 2 { 1 - *symbol.id() } else { *symbol.id() }))
+        }));
+        assert!(result.is_err());
+        for (id, v) in ["<pad>", "<s>", "a", "b"].iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+
+        // Moving the fallback to id 0 would renumber the reserved symbols, so
+        // the index is handed back without the fallback.
+        let mut i = FrozenIndexing::new(i, "<unk>").unwrap_err();
+        assert_eq!(i.table().len(), 4);
+        assert!(i.get("<unk>").is_none());
+        assert!(i.get_symbol(&4).is_none());
+        for (id, v) in ["<pad>", "<s>", "a", "b"].iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+        assert_eq!(*i.get_or_insert("<unk>").unwrap().id(), 4);
+
+        // A fallback that was already present is left in place.
+        let i = FrozenIndexing::new(i, "<unk>").unwrap_err();
+        assert_eq!(i.get_symbol_id("<unk>"), Some(4));
+        let f = FrozenIndexing::with_fallback_id(i, 4).unwrap();
+        assert_eq!(f.encode_or_unk(&["<s>", "z"]), vec![1, 4]);
+    }
+
+    #[test]
+    fn hash_indexing_merge_ok() {
+        let mut i = HashIndexing::<String, usize>::default();
+        i.encode_or_insert(vec!["a".to_string(), "b".to_string()]);
+        let mut other = HashIndexing::<String, usize>::default();
+        other.encode_or_insert(vec!["c".to_string(), "a".to_string(), "d".to_string()]);
+        assert_eq!(i.merge(other), vec![Some(2), Some(0), Some(3)]);
+        assert_eq!(i.table().len(), 4);
+        for (id, v) in ["a", "b", "c", "d"].iter().enumerate() {
+            assert_eq!(i.get_symbol_id(*v), Some(id));
+        }
+
+        let mut t = Table::<String, usize>::new();
+        t.insert_all(vec!["e".to_string(), "b".to_string(), "e".to_string()]);
+        assert_eq!(i.merge(HashIndexing::<String, usize>::from_table(t)),
+                   vec![Some(4), Some(1), Some(4)]);
+        assert_eq!(i.merge(HashIndexing::<String, usize>::default()), Vec::<Option<usize>>::new());
+    }
+
+    #[test]
+    fn hash_indexing_merge_tombstones_ok() {
+        let mut previous = HashIndexing::<&str, usize>::default();
+        previous.encode_or_insert(vec!["a", "b", "c", "d"]);
+        let mut other = HashIndexing::<&str, usize>::default();
+        other.encode_or_insert(vec!["d", "e", "b", "_"]);
+        other.retain(|symbol| *symbol.data() != "e");
+        other.align_to(&previous, AlignPolicy::Tombstone("_"));
+        // "b" and "d" keep ids 1 and 3, the real "_" goes after them, and ids 0
+        // and 2 hold tombstones.
+        assert_eq!(other.tombstones, vec![0, 2]);
+        assert_eq!(other.get_symbol_id("_"), Some(4));
+
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["d", "z"]);
+        assert_eq!(i.merge(other), vec![None, Some(2), None, Some(0), Some(3)]);
+        assert_eq!(i.table().len(), 4);
+        for (id, v) in ["d", "z", "b", "_"].iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+        }
+
+        // Ids that were never assigned map to nothing.
+        let mut other = HashIndexing::<&str, usize>::default();
+        other.encode_or_insert(vec!["y", "b"]);
+        other.remap(|symbol| Some(2 * *symbol.id()));
+        assert_eq!(i.merge(other), vec![Some(4), None, Some(2)]);
+        assert_eq!(i.get_symbol_id("y"), Some(4));
+    }
+
+    #[test]
+    fn hash_indexing_merge_exhausted_panics_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut i = HashIndexing::<u16, u8>::default();
+        i.encode_or_insert(0..254);
+        let mut other = HashIndexing::<u16, u8>::default();
+        other.encode_or_insert(vec![0, 300, 1, 301, 300, 302]);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.merge(other)));
+        assert!(result.is_err());
+        assert_eq!(i.table().len(), 254);
+        assert_eq!(i.get_symbol_id(&300), None);
+
+        // Values already present don't need ids, and repeated ones need one.
+        let mut other = HashIndexing::<u16, u8>::default();
+        other.encode_or_insert(vec![300, 0, 300, 301]);
+        assert_eq!(i.merge(other), vec![Some(254), Some(0), Some(255)]);
+        assert_eq!(i.table().len(), 256);
+    }
+
+    /// Aligns an index of `current` to an index of `previous`, returning the
+    /// translation and the aligned values in id order.
+    fn aligned(previous: &[&'static str], current: &[&'static str],
+               policy: AlignPolicy<&'static str>) -> (Vec<usize>, Vec<&'static str>) {
+        let mut p = HashIndexing::<&str, usize>::default();
+        p.encode_or_insert(previous.iter().cloned());
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(current.iter().cloned());
+        let translation = i.align_to(&p, policy).into_iter().map(Option::unwrap).collect();
+        let values = i.table().iter().rev().map(|s| *s.data()).collect();
+        for symbol in i.table().iter() {
+            assert_eq!(i.is_tombstone(symbol.id()), *symbol.data() == "_");
+            if !i.is_tombstone(symbol.id()) {
+                assert_eq!(i.get_symbol_id(symbol.data()), Some(*symbol.id()));
+            }
+        }
+        assert!(i.get("_").is_none());
+        (translation, values)
+    }
+
+    #[test]
+    fn align_to_tombstone_ok() {
+        assert_eq!(aligned(&["a", "b", "c", "d"], &["d", "e", "b", "f"],
+                           AlignPolicy::Tombstone("_")),
+                   (vec![3, 4, 1, 5], vec!["_", "b", "_", "d", "e", "f"]));
+        assert_eq!(aligned(&["a", "b", "c"], &["c", "a"], AlignPolicy::Tombstone("_")),
+                   (vec![2, 0], vec!["a", "_", "c"]));
+        // Trailing ids that are no longer used are not kept.
+        assert_eq!(aligned(&["a", "b", "c"], &["a"], AlignPolicy::Tombstone("_")),
+                   (vec![0], vec!["a"]));
+        assert_eq!(aligned(&[], &["b", "a"], AlignPolicy::Tombstone("_")),
+                   (vec![0, 1], vec!["b", "a"]));
+        assert_eq!(aligned(&["a"], &[], AlignPolicy::Tombstone("_")), (vec![], vec![]));
+    }
+
+    #[test]
+    fn align_to_reuse_ok() {
+        assert_eq!(aligned(&["a", "b", "c", "d"], &["d", "e", "b", "f"], AlignPolicy::Reuse("_")),
+                   (vec![3, 0, 1, 2], vec!["e", "b", "f", "d"]));
+        assert_eq!(aligned(&["a", "b", "c", "d", "e"], &["e", "x"], AlignPolicy::Reuse("_")),
+                   (vec![4, 0], vec!["x", "_", "_", "_", "e"]));
+        assert_eq!(aligned(&["a", "b"], &["c", "d", "e"], AlignPolicy::Reuse("_")),
+                   (vec![0, 1, 2], vec!["c", "d", "e"]));
+    }
+
+    #[test]
+    fn align_to_keeps_reserved_ok() {
+        let mut p = HashIndexing::<&str, u8>::default();
+        p.encode_or_insert(vec!["x", "a", "<pad>"]);
+        let mut i = HashIndexing::<&str, u8>::with_reserved(&["<pad>"]);
+        i.encode_or_insert(vec!["a", "y"]);
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone("_")), vec![Some(0), Some(1), Some(3)]);
+        assert!(i.is_reserved(&0));
+        assert_eq!(i.get_symbol_id("<pad>"), Some(0));
+        assert_eq!(i.get_symbol_id("a"), Some(1));
+        assert_eq!(i.get_symbol(&2).unwrap().data(), &"_");
+        assert_eq!(i.get_symbol_id("y"), Some(3));
+        assert_eq!(i.table().len(), 4);
+    }
+
+    #[test]
+    fn align_to_tombstones_not_indexed_ok() {
+        let mut p = HashIndexing::<&str, usize>::default();
+        p.encode_or_insert(vec!["a", "b", "c", "d"]);
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["d", "a"]);
+        i.align_to(&p, AlignPolicy::Tombstone("_"));
+        assert!(i.is_tombstone(&1) && i.is_tombstone(&2));
+        assert!(i.get("_").is_none());
+
+        // Aligning again drops the old tombstones rather than treating them as
+        // values, and inserts new ones.
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone("_")),
+                   vec![Some(0), None, None, Some(3)]);
+        assert_eq!(i.table().iter().rev().map(|s| *s.data()).collect::<Vec<_>>(),
+                   vec!["a", "_", "_", "d"]);
+        assert!(i.is_tombstone(&1) && i.is_tombstone(&2));
+
+        // A tombstone's value can still be inserted as a real symbol.
+        assert_eq!(i.get_or_insert("_").map(|s| *s.id()), Insertion::New(4));
+        assert_eq!(i.get_symbol_id("_"), Some(4));
+        assert!(!i.is_tombstone(&4));
+
+        // Tombstones follow their symbols when the index is renumbered.
+        i.remap(|s| Some(4 - *s.id()));
+        assert_eq!(i.tombstones, vec![2, 3]);
+        assert_eq!(i.get_symbol_id("_"), Some(0));
+        i.retain(|s| *s.id() != 2);
+        assert_eq!(i.tombstones, vec![2]);
+        assert_eq!(i.get_symbol_id("a"), Some(3));
+        assert_eq!(i.get_symbol_id("_"), Some(0));
+
+        let frozen = i.freeze();
+        assert!(frozen.is_tombstone(&2));
+        assert_eq!(frozen.get_symbol_id("_"), Some(0));
+        let i = frozen.thaw();
+        assert!(i.is_tombstone(&2));
+        assert_eq!(i.get_symbol_id("_"), Some(0));
+    }
+
+    #[test]
+    fn align_to_previous_with_gaps_ok() {
+        let mut p = HashIndexing::<&str, usize>::default();
+        p.encode_or_insert(vec!["a", "b", "c"]);
+        p.remap(|s| if *s.data() == "b" { None } else { Some(*s.id()) });
+        assert_eq!(p.table().len(), 2);
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["c", "a", "d"]);
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone("_")), vec![Some(2), Some(0), Some(3)]);
+        assert_eq!(i.table().iter().rev().map(|s| *s.data()).collect::<Vec<_>>(),
+                   vec!["a", "_", "c", "d"]);
+    }
+
+    #[test]
+    fn align_to_sparse_ids_ok() {
+        let mut p = HashIndexing::<&str, usize>::default();
+        p.encode_or_insert(vec!["a", "b"]);
+        let mut i = HashIndexing::<&str, usize>::default();
+        i.encode_or_insert(vec!["x", "b", "a"]);
+        i.remap(|s| if *s.data() == "x" { None } else { Some(*s.id()) });
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone("_")), vec![None, Some(1), Some(0)]);
+        assert_eq!(i.get_symbol_id("a"), Some(0));
+        assert_eq!(i.get_symbol_id("b"), Some(1));
+        assert_eq!(i.table().len(), 2);
+    }
+
+    #[test]
+    fn align_to_again_all_ids_ok() {
+        let mut p = HashIndexing::<u16, u8>::default();
+        p.encode_or_insert(0..256);
+        let mut i = HashIndexing::<u16, u8>::default();
+        i.get_or_insert(255);
+        let mut expected = vec![None; 255];
+        expected.push(Some(255));
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone(1000)), vec![Some(255)]);
+        assert_eq!(i.table().len(), 256);
+
+        // Every id is taken, so the old tombstones must be dropped before the
+        // new ones are inserted.
+        assert_eq!(i.align_to(&p, AlignPolicy::Tombstone(1000)), expected);
+        assert_eq!(i.table().len(), 256);
+        assert_eq!(i.tombstones, (0..255).collect::<Vec<u8>>());
+        assert_eq!(i.get_symbol_id(&255), Some(255));
+        assert_eq!(i.get_symbol(&0).unwrap().data(), &1000);
+        assert!(i.get(&1000).is_none());
+    }
+
+    #[test]
+    fn hash_indexing_capacity_ok() {
+        let mut i = HashIndexing::<usize, usize>::with_capacity(100);
+        assert!(i.capacity() >= 100);
+        assert!(i.by_symbol.capacity() >= 100);
+        for v in VALUES.iter() {
+            i.get_or_insert(*v);
+        }
+        i.reserve(5000);
+        assert!(i.capacity() >= VALUES.len() + 5000);
+        i.shrink_to_fit();
+        assert!(i.capacity() >= VALUES.len());
+        assert!(i.capacity() < VALUES.len() + 5000);
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+        }
+
+        let mut i = HashIndexing::<usize, usize>::default();
+        assert_eq!(i.capacity(), 0);
+        i.shrink_to_fit();
+        assert_eq!(i.capacity(), 0);
+    }
+
+    #[test]
+    fn hash_indexing_with_hasher_ok() {
+        type FixedState = BuildHasherDefault<DefaultHasher>;
+
+        let mut i = HashIndexing::<String, usize, FixedState>::with_hasher(FixedState::default());
+        for v in ["NNP", "VBD", "NNP"].iter() {
+            i.get_or_insert_borrowed(*v);
+        }
+        assert_eq!(i.table().len(), 2);
+        assert_eq!(i.get_symbol_id("VBD"), Some(1));
+
+        let i = HashIndexing::<usize, usize, FixedState>::with_capacity_and_hasher(
+            100, FixedState::default());
+        assert!(i.by_symbol.capacity() >= 100);
+
+        let mut t = Table::<usize, usize>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let i = HashIndexing::from_table_with_hasher(t, FixedState::default());
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v), Some(id));
+        }
+
+        let mut i = CountingIndexing::<usize, u8, FixedState>::default();
+        i.get_or_insert(7);
+        i.get_or_insert(7);
+        assert_eq!(i.count(&0), Some(2));
+    }
+
+    #[test]
+    fn freeze_ok() {
+        let mut i = HashIndexing::<String, u16>::with_reserved(&["<unk>".to_string()]);
+        i.encode_or_insert(VALUES.iter().map(|v| v.to_string()));
+        let f = i.freeze();
+        assert_eq!(f.len(), VALUES.len() + 1);
+        assert!(f.slots.len() > 2 * f.len());
+        assert_eq!(f.get_symbol_id("<unk>"), Some(0));
+        for (id, v) in VALUES.iter().enumerate() {
+            let id = id as u16 + 1;
+            let symbol = f.get(v.to_string().as_str()).unwrap();
+            assert_eq!(symbol.id(), &id);
+            assert_eq!(f.get_symbol(&id).unwrap().data(), &v.to_string());
+        }
+        assert!(f.get("missing").is_none());
+        assert!(f.get_symbol(&(VALUES.len() as u16 + 1)).is_none());
+        assert!(f.is_reserved(&0));
+        assert!(!f.is_reserved(&1));
+
+        let mut i = f.thaw();
+        assert_eq!(i.table().len(), VALUES.len() + 1);
+        assert!(i.is_reserved(&0));
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v.to_string().as_str()), Some(id as u16 + 1));
+        }
+        let id = *i.get_or_insert("missing".to_string()).unwrap().id();
+        assert_eq!(id as usize, VALUES.len() + 1);
+    }
+
+    #[test]
+    fn freeze_sparse_ids_ok() {
+        let mut i = HashIndexing::<usize, usize>::default();
+        i.encode_or_insert(VALUES.iter().cloned());
+        i.remap(|symbol| if *symbol.id() == 1 { None } else { Some(*symbol.id() * 2) });
+        let f = i.freeze();
+        assert_eq!(f.len(), VALUES.len() - 1);
+        assert!(f.get_symbol(&1).is_none());
+        assert!(f.get_symbol(&2).is_none());
+        for (id, v) in VALUES.iter().enumerate().filter(|&(id, _)| id != 1) {
+            assert_eq!(f.get_symbol(&(id * 2)).unwrap().data(), v);
+            assert_eq!(f.get_symbol_id(v), Some(id * 2));
+        }
+
+        let i = f.thaw();
+        assert_eq!(i.table().len(), VALUES.len() - 1);
+        for (id, v) in VALUES.iter().enumerate().filter(|&(id, _)| id != 1) {
+            assert_eq!(i.get_symbol(&(id * 2)).unwrap().data(), v);
+            assert_eq!(i.get_symbol_id(v), Some(id * 2));
+        }
+    }
+
+    #[test]
+    fn freeze_empty_ok() {
+        let f = HashIndexing::<usize, usize>::default().freeze();
+        assert!(f.is_empty());
+        assert!(f.get(&0).is_none());
+        assert!(f.get_symbol(&0).is_none());
+        assert!(f.thaw().table().is_empty());
+    }
+
+    #[test]
+    fn frozen_send_to_thread_ok() {
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_sync<X>(_: &X) where X: Sync { }
+
+        let mut i = HashIndexing::<usize, usize>::default();
+        i.encode_or_insert(VALUES.iter().cloned());
+        let f = Arc::new(i.freeze());
+        assert_sync(&*f);
+        let threads: Vec<_> = (0..4).map(|_| {
+            let f = f.clone();
+            thread::spawn(move || VALUES.iter().map(|v| f.get_symbol_id(v)).collect::<Vec<_>>())
+        }).collect();
+        for t in threads {
+            let ids = t.join().unwrap();
+            assert_eq!(ids, (0..VALUES.len()).map(Some).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn send_to_thread_safe_ok() {
+        use std::sync::Arc;
+        use std::thread;
 
         let mut t = Table::<usize, usize>::new();
         for v in VALUES.iter() {
             t.insert(*v);
         }
-        let index = Arc::new(HashIndexing::from_table(t));
+        let index = Arc::new(HashIndexing::<usize, usize>::from_table(t));
         {
             let id1 = index.get(&VALUES[0]).unwrap().id().clone();
             let id2 = index.get(&VALUES[1]).unwrap().id().clone();
@@ -453,6 +3104,167 @@ mod test {
         }
     }
 
+    #[test]
+    fn concurrent_from_table_ok() {
+        let mut t = Table::<String, usize>::new();
+        for v in VALUES.iter() {
+            t.insert(v.to_string());
+        }
+        let i = ConcurrentHashIndexing::<String, usize>::from_table(t);
+        assert_eq!(i.len(), VALUES.len());
+        for (id, v) in VALUES.iter().enumerate() {
+            assert_eq!(i.get_symbol_id(v.to_string().as_str()), Some(id));
+            assert_eq!(i.get_symbol(&id).unwrap().data(), &v.to_string());
+        }
+        assert!(i.get("missing").is_none());
+        assert!(i.get_symbol(&VALUES.len()).is_none());
+        match i.get_or_insert(VALUES[2].to_string()) {
+            Insertion::Present(symbol) => assert_eq!(*symbol.id(), 2),
+            x => panic!("{:?}", x),
+        }
+        match i.get_or_insert("missing".to_string()) {
+            Insertion::New(symbol) => assert_eq!(*symbol.id(), VALUES.len()),
+            x => panic!("{:?}", x),
+        }
+        let t = i.to_table();
+        assert_eq!(t.len(), VALUES.len() + 1);
+        assert_eq!(t.get(&VALUES.len()).unwrap().data(), "missing");
+    }
+
+    #[test]
+    fn concurrent_try_get_or_insert_exhausted_ok() {
+        let i = ConcurrentHashIndexing::<usize, u8>::with_shards(1);
+        for v in 0..256 {
+            assert!(i.try_get_or_insert(v).is_ok());
+        }
+        assert!(i.try_get_or_insert(0).is_ok());
+        assert_eq!(i.try_get_or_insert(256).unwrap_err().into_value(), 256);
+        assert_eq!(i.len(), 256);
+    }
+
+    #[test]
+    #[should_panic]
+    fn concurrent_zero_shards_panics() {
+        ConcurrentHashIndexing::<usize, usize>::with_shards(0);
+    }
+
+    #[test]
+    fn concurrent_get_or_insert_ok() {
+        use ::crossbeam;
+
+        const THREADS: usize = 8;
+        const VALUES_PER_THREAD: usize = 3000;
+
+        let index = ConcurrentHashIndexing::<usize, usize>::default();
+        let first = index.get_or_insert(VALUES_PER_THREAD).unwrap();
+        let first_address = first as *const _;
+        let index = &index;
+        let assignments: Vec<Vec<(usize, usize)>> = crossbeam::scope(|scope| {
+            let handles: Vec<_> = (0..THREADS).map(|t| scope.spawn(move || {
+                // Each thread visits the values in a different order, so that
+                // threads race to insert them.
+                (0..VALUES_PER_THREAD).map(|i| {
+                    let v = (i * 7 + t * 389) % VALUES_PER_THREAD;
+                    (v, *index.get_or_insert(v).unwrap().id())
+                }).collect()
+            })).collect();
+            handles.into_iter().map(|h| h.join()).collect()
+        });
+
+        assert_eq!(index.len(), VALUES_PER_THREAD + 1);
+        for assignment in assignments {
+            for (v, id) in assignment {
+                assert_eq!(index.get_symbol_id(&v), Some(id));
+                assert_eq!(*index.get_symbol(&id).unwrap().data(), v);
+            }
+        }
+        let mut ids: Vec<usize> =
+            (0..VALUES_PER_THREAD).map(|v| index.get_symbol_id(&v).unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, (1..VALUES_PER_THREAD + 1).collect::<Vec<_>>());
+        // The symbol inserted first has not moved.
+        assert_eq!(*first.data(), VALUES_PER_THREAD);
+        assert_eq!(index.get(&VALUES_PER_THREAD).unwrap() as *const _, first_address);
+    }
+
+    #[test]
+    fn sharded_merge_ok() {
+        let mut i = ShardedIndexing::<&str, usize>::with_global(
+            HashIndexing::with_reserved(&["<unk>"]), 3);
+        i.shards_mut()[0].encode_or_insert(vec!["b", "a", "b"]);
+        i.shards_mut()[2].encode_or_insert(vec!["c", "a", "<unk>"]);
+        let translations = i.merge();
+        assert_eq!(translations,
+                   vec![vec![Some(1), Some(2)], vec![], vec![Some(3), Some(2), Some(0)]]);
+        assert!(i.shards_mut().iter().all(|shard| shard.table().is_empty()));
+        assert_eq!(i.global().table().len(), 4);
+        assert!(i.global().is_reserved(&0));
+
+        i.shards_mut()[1].encode_or_insert(vec!["d", "c"]);
+        assert_eq!(i.merge(), vec![vec![], vec![Some(4), Some(3)], vec![]]);
+        assert_eq!(i.merge(), vec![Vec::<Option<usize>>::new(); 3]);
+
+        i.shards_mut()[0].get_or_insert("e");
+        let global = i.into_global();
+        assert_eq!(global.get_symbol_id("e"), Some(5));
+    }
+
+    #[test]
+    fn sharded_merge_exhausted_panics_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut global = HashIndexing::<u16, u8>::default();
+        global.encode_or_insert(0..250);
+        let mut i = ShardedIndexing::with_global(global, 2);
+        i.shards_mut()[0].encode_or_insert(vec![0, 300, 301, 300]);
+        i.shards_mut()[1].encode_or_insert(vec![302, 1, 303, 304, 305, 301]);
+        // The first shard fits, but both together need 7 new ids and only 6
+        // are left.
+        i.shards_mut()[1].get_or_insert(306);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| i.merge()));
+        assert!(result.is_err());
+        assert_eq!(i.global().table().len(), 250);
+        assert_eq!(i.global().get_symbol_id(&300), None);
+        assert_eq!(i.shards_mut()[0].get_symbol_id(&301), Some(2));
+        assert_eq!(i.shards_mut()[1].table().len(), 7);
+
+        // Values shared between shards only need one id.
+        i.shards_mut()[1].retain(|symbol| *symbol.data() != 306);
+        let translations = i.merge();
+        assert_eq!(translations, vec![vec![Some(0), Some(250), Some(251)],
+                                      vec![Some(252), Some(1), Some(253), Some(254),
+                                           Some(255), Some(251)]]);
+        assert_eq!(i.global().table().len(), 256);
+        assert!(i.shards_mut().iter().all(|shard| shard.table().is_empty()));
+    }
+
+    #[test]
+    fn sharded_merge_deterministic_ok() {
+        use ::crossbeam;
+
+        fn ingest(shards: usize) -> (Vec<Vec<Option<usize>>>, Vec<usize>) {
+            let mut i = ShardedIndexing::<usize, usize>::with_shards(shards);
+            crossbeam::scope(|scope| {
+                for (t, shard) in i.shards_mut().iter_mut().enumerate() {
+                    scope.spawn(move || {
+                        for n in 0..2000 {
+                            shard.get_or_insert((n * 31 + t * 17) % 1500);
+                        }
+                    });
+                }
+            });
+            let translations = i.merge();
+            let order = i.global().table().iter().rev().map(|s| *s.data()).collect();
+            (translations, order)
+        }
+
+        let (translations, order) = ingest(6);
+        assert_eq!(order.len(), 1500);
+        for _ in 0..3 {
+            assert_eq!(ingest(6), (translations.clone(), order.clone()));
+        }
+    }
+
     #[test]
     fn sync_to_thread_ok() {
         use ::crossbeam;
@@ -461,7 +3273,7 @@ mod test {
         for v in VALUES.iter() {
             t.insert(*v);
         }
-        let index = HashIndexing::from_table(t);
+        let index = HashIndexing::<usize, usize>::from_table(t);
         let id1 = *index.get(&VALUES[0]).unwrap().id();
         let id2 = *index.get(&VALUES[1]).unwrap().id();
         let index = &index;
@@ -11,6 +11,21 @@
 //! HashMap-backed index is provided in
 //! [HashIndexing](indexing/struct.HashIndexing.html).
 //!
+//! Two versions of a vocabulary can be compared with the
+//! [diff](diff/index.html) module.
+//!
+//! Tables may be saved to and loaded from a compact binary format with
+//! `Table::write_to()` and `Table::read_from()`, as described in the
+//! [binary](binary/index.html) module.
+//!
+//! With the `mmap` cargo feature enabled, string tables in that format can be
+//! memory-mapped for read-only lookup by [MmapTable](mmap/struct.MmapTable.html).
+//!
+//! When the `serde` cargo feature is enabled, `Table` and `HashIndexing`
+//! implement serde's `Serialize` and `Deserialize`. Tables are written as a
+//! sequence of values in id order, and ids are preserved when they are read
+//! back.
+//!
 //! # Example
 //!
 //! ```
@@ -50,9 +65,18 @@
 //! assert!(assoc1 == assoc3);
 //! ```
 
+pub mod binary;
+pub mod diff;
 pub mod indexing;
 mod table;  // Not pub because all pub symbols re-exported.
+#[cfg(feature = "mmap")] pub mod mmap;
+#[cfg(feature = "serde")] mod serde_impls;
 
+extern crate hashbrown;
 #[cfg(test)] extern crate crossbeam;
+#[cfg(feature = "mmap")] extern crate memmap2;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
 
-pub use self::table::{Symbol, SymbolId, Table, TableIntoIter, TableIter};
+pub use self::table::{CapacityError, Extension, RemapError, Symbol, SymbolId, Table, TableIntoIter,
+                      TableIter};
@@ -0,0 +1,439 @@
+//! Read-only string tables backed by a memory-mapped file, enabled by the
+//! `mmap` cargo feature.
+//!
+//! An [MmapTable](struct.MmapTable.html) maps a file written by
+//! `Table::<String, D>::write_to()` and looks values up directly in the mapped
+//! bytes, using the index stored in the file. Opening a table only checks its
+//! header, so it takes the same time however many symbols the table holds, and
+//! no symbol values are copied or allocated.
+//!
+//! # Example
+//!
+//! ```
+//! use symbol_map::Table;
+//! use symbol_map::mmap::MmapTable;
+//! use std::fs::File;
+//!
+//! let mut table = Table::<String, u32>::new();
+//! table.insert("NNP".to_string());
+//! table.insert("VBD".to_string());
+//! let path = std::env::temp_dir()
+//!     .join(format!("symbol_map_mmap_doc_example_{}.bin", std::process::id()));
+//! table.write_to(File::create(&path).unwrap()).unwrap();
+//!
+//! // Safety: nothing else modifies the file while it is mapped.
+//! let mapped = unsafe { MmapTable::<u32>::open(&path) }.unwrap();
+//! assert_eq!(*mapped.get("VBD").unwrap().unwrap().id(), 1);
+//! assert_eq!(mapped.get_symbol(&0).unwrap().unwrap().data(), "NNP");
+//! # drop(mapped);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::str;
+
+use memmap2::Mmap;
+
+use binary::{self, BinaryError, BinaryId, DecodeError, HEADER_LEN};
+
+/// A symbol in an `MmapTable`, borrowing its value from the mapped file.
+///
+/// This mirrors the accessors of `Symbol`, so that code which only calls
+/// `id()` and `data()` on the results of `Indexing::get()` and
+/// `Indexing::get_symbol()` works against an `MmapTable` once lookup errors
+/// are handled.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MmapSymbol<'a, D> where D: BinaryId {
+    id: D,
+    data: &'a str,
+}
+
+impl<'a, D> MmapSymbol<'a, D> where D: BinaryId {
+    /// Returns the symbol's ID.
+    pub fn id(&self) -> &D {
+        &self.id
+    }
+
+    /// Returns the symbol's value.
+    pub fn data(&self) -> &'a str {
+        self.data
+    }
+}
+
+/// A read-only, memory-mapped table of strings that supports lookup in both
+/// directions.
+///
+/// Values and index entries are checked as they are read, rather than when the
+/// table is opened, so lookups return an error if they reach a corrupt part of
+/// the file. Use `validate()` to check the whole file up front.
+#[derive(Debug)]
+pub struct MmapTable<D> where D: BinaryId {
+    bytes: Mmap,
+    /// Number of symbols in the table.
+    len: usize,
+    /// Position in `bytes` of the first value, after the index.
+    values_start: usize,
+    /// Position in `bytes` of the end of the last value.
+    values_end: usize,
+    marker: PhantomData<D>,
+}
+
+impl<D> MmapTable<D> where D: BinaryId {
+    /// Maps the binary table at `path`, which must have been written with ids
+    /// of type `D` and values of type `String`.
+    ///
+    /// Returns an error if the file cannot be mapped, does not start with a
+    /// valid header for the current format version, or has no index sorted by
+    /// value.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other
+    /// process, while the table is open. Its contents are read as ordinary
+    /// memory, so changing them is undefined behavior.
+    pub unsafe fn open<P>(path: P) -> Result<Self, BinaryError> where P: AsRef<Path> {
+        let file = File::open(path)?;
+        // The caller guarantees that the file is not modified while mapped.
+        let bytes = Mmap::map(&file)?;
+        Self::from_mmap(bytes)
+    }
+
+    /// Wraps a mapped binary table, checking its header.
+    pub fn from_mmap(bytes: Mmap) -> Result<Self, BinaryError> {
+        let (version, len) = binary::read_header::<_, D>(&mut &bytes[..])?;
+        if version != binary::VERSION {
+            return Err(BinaryError::UnsupportedVersion(version))
+        }
+        // Every symbol takes up 16 bytes of the index and at least the 8 bytes
+        // of its length prefix, so a corrupt count is caught here rather than
+        // by an overflow.
+        if len > ((bytes.len() - HEADER_LEN) / 24) as u64 {
+            return Err(BinaryError::Truncated)
+        }
+        let len = len as usize;
+        let values_start = HEADER_LEN + 8 * (2 * len + 2);
+        if values_start > bytes.len() {
+            return Err(BinaryError::Truncated)
+        }
+        if u64_at(&bytes, HEADER_LEN + 8 * (len + 1)) != len as u64 {
+            return Err(BinaryError::CorruptIndex)
+        }
+        let values_end = u64_at(&bytes, HEADER_LEN + 8 * len);
+        if values_end > bytes.len() as u64 {
+            return Err(BinaryError::Truncated)
+        }
+        if values_end < (values_start + 8 * len) as u64 {
+            return Err(BinaryError::CorruptIndex)
+        }
+        Ok(MmapTable {
+            bytes,
+            len,
+            values_start,
+            values_end: values_end as usize,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns the number of symbols in the table.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` iff the table contains no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Looks up `data` in the table. Returns `Ok(Some(symbol))` if a symbol is
+    /// present, else `Ok(None)`.
+    ///
+    /// Returns an error if the part of the file that is read is corrupt.
+    pub fn get(&self, data: &str) -> Result<Option<MmapSymbol<'_, D>>, BinaryError> {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let index = self.index_by_value(mid)?;
+            let value = self.value(index)?;
+            match value.cmp(data) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(Some(self.symbol(index, value))),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Looks up the symbol with id `id`. Returns `Ok(Some(symbol))` if a
+    /// symbol is present, else `Ok(None)`.
+    ///
+    /// Returns an error if the value of the symbol is corrupt.
+    pub fn get_symbol(&self, id: &D) -> Result<Option<MmapSymbol<'_, D>>, BinaryError> {
+        if id.as_usize() < self.len {
+            Ok(Some(MmapSymbol { id: *id, data: self.value(id.as_usize())?, }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Checks every value and index entry in the file, so that later lookups
+    /// cannot return an error. This takes time proportional to the size of the
+    /// table.
+    ///
+    /// Returns an error if a value is not valid UTF-8, or the index does not
+    /// list every symbol in order of their values.
+    pub fn validate(&self) -> Result<(), BinaryError> {
+        for index in 0..self.len {
+            self.value(index)?;
+        }
+        let mut seen = vec![false; self.len];
+        let mut previous = None;
+        for position in 0..self.len {
+            let index = self.index_by_value(position)?;
+            let value = self.value(index)?;
+            if seen[index] || previous.is_some_and(|previous| previous > value) {
+                return Err(BinaryError::CorruptIndex)
+            }
+            seen[index] = true;
+            previous = Some(value);
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the symbol at `index` in id order, which must be
+    /// less than `self.len`.
+    fn value(&self, index: usize) -> Result<&str, BinaryError> {
+        let start = u64_at(&self.bytes, HEADER_LEN + 8 * index);
+        if start < (self.values_start + 8) as u64 || start > self.values_end as u64 {
+            return Err(BinaryError::CorruptIndex)
+        }
+        let start = start as usize;
+        let end = match u64_at(&self.bytes, start - 8).checked_add(start as u64) {
+            Some(end) if end <= self.values_end as u64 => end as usize,
+            _ => return Err(BinaryError::CorruptIndex),
+        };
+        str::from_utf8(&self.bytes[start..end]).map_err(|e| BinaryError::InvalidValue {
+            index: index as u64, error: DecodeError::new(e.to_string()),
+        })
+    }
+
+    /// Returns the index in id order of the symbol at `position` in order of
+    /// value, which must be less than `self.len`.
+    fn index_by_value(&self, position: usize) -> Result<usize, BinaryError> {
+        let index = u64_at(&self.bytes, HEADER_LEN + 8 * (self.len + 2 + position));
+        if index >= self.len as u64 {
+            return Err(BinaryError::CorruptIndex)
+        }
+        Ok(index as usize)
+    }
+
+    /// Returns the symbol at `index` in id order, which has value `data`.
+    fn symbol<'a>(&self, index: usize, data: &'a str) -> MmapSymbol<'a, D> {
+        // index is less than len, which was checked to fit in D by
+        // read_header().
+        MmapSymbol { id: D::from_u64(index as u64).unwrap(), data, }
+    }
+}
+
+/// Reads the little-endian `u64` at `position`, which must be in bounds.
+fn u64_at(bytes: &[u8], position: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[position..position + 8]);
+    u64::from_le_bytes(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::MmapTable;
+    use binary::{BinaryError, BinaryId, IdWidth, MAGIC};
+    use ::Table;
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    const VALUES: &[&str] = &["NNP", "VBD", "", "DT", "NN", "IN", "ünïcödé"];
+
+    /// Position of the first value written by `table_bytes()`, after the
+    /// header and the 8 positions, count and 7 ids of the index.
+    const VALUES_START: usize = 21 + 8 * 16;
+
+    /// Writes `bytes` to a fresh file in the temporary directory, which is
+    /// removed when the returned guard is dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, bytes: &[u8]) -> Self {
+            let path = env::temp_dir().join(format!("symbol_map_{}_{}.bin", name,
+                                                    ::std::process::id()));
+            File::create(&path).unwrap().write_all(bytes).unwrap();
+            TempFile(path)
+        }
+
+        fn open<D>(&self) -> Result<MmapTable<D>, BinaryError> where D: BinaryId {
+            // Safety: the file is only written before it is mapped.
+            unsafe { MmapTable::open(&self.0) }
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn table_bytes() -> Vec<u8> {
+        let mut t = Table::<String, u16>::new();
+        for v in VALUES.iter() {
+            t.insert(v.to_string());
+        }
+        let mut bytes = Vec::new();
+        t.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn lookup_ok() {
+        let file = TempFile::new("lookup_ok", &table_bytes());
+        let t = file.open::<u16>().unwrap();
+        t.validate().unwrap();
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            let id = i as u16;
+            assert_eq!(t.get_symbol(&id).unwrap().unwrap().data(), *v);
+            assert_eq!(t.get_symbol(&id).unwrap().unwrap().id(), &id);
+            assert_eq!(t.get(v).unwrap().unwrap().id(), &id);
+            assert_eq!(t.get(v).unwrap().unwrap().data(), *v);
+        }
+        assert!(t.get("VB").unwrap().is_none());
+        assert!(t.get("zzz").unwrap().is_none());
+        assert!(t.get_symbol(&(VALUES.len() as u16)).unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_ok() {
+        let mut bytes = Vec::new();
+        Table::<String, u8>::new().write_to(&mut bytes).unwrap();
+        let file = TempFile::new("empty_ok", &bytes);
+        let t = file.open::<u8>().unwrap();
+        t.validate().unwrap();
+        assert!(t.is_empty());
+        assert!(t.get("").unwrap().is_none());
+        assert!(t.get_symbol(&0).unwrap().is_none());
+    }
+
+    #[test]
+    fn id_width_mismatch_err() {
+        let file = TempFile::new("id_width_mismatch_err", &table_bytes());
+        match file.open::<u32>() {
+            Err(BinaryError::IdWidthMismatch { .. }) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn version_1_err() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(IdWidth::U16.tag());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        let file = TempFile::new("version_1_err", &bytes);
+        match file.open::<u16>() {
+            Err(BinaryError::UnsupportedVersion(1)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn truncated_err() {
+        let bytes = table_bytes();
+        for len in 0..bytes.len() {
+            let file = TempFile::new("truncated_err", &bytes[..len]);
+            match file.open::<u16>() {
+                Err(BinaryError::Truncated) => (),
+                x => panic!("length {}: {:?}", len, x),
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_count_err() {
+        let mut bytes = table_bytes();
+        bytes[13..21].copy_from_slice(&60000u64.to_le_bytes());
+        let file = TempFile::new("corrupt_count_err", &bytes);
+        match file.open::<u16>() {
+            Err(BinaryError::Truncated) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn corrupt_index_err() {
+        // The position of the value of symbol 1 is past the end of the values.
+        let mut bytes = table_bytes();
+        let past_end = bytes.len() as u64 + 1;
+        bytes[29..37].copy_from_slice(&past_end.to_le_bytes());
+        let file = TempFile::new("corrupt_index_err", &bytes);
+        let t = file.open::<u16>().unwrap();
+        match t.validate() {
+            Err(BinaryError::CorruptIndex) => (),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(t.get_symbol(&0).unwrap().unwrap().data(), "NNP");
+        match t.get_symbol(&1) {
+            Err(BinaryError::CorruptIndex) => (),
+            x => panic!("{:?}", x),
+        }
+        match t.get("VBD") {
+            Err(BinaryError::CorruptIndex) => (),
+            x => panic!("{:?}", x),
+        }
+
+        // Symbol 0 is listed twice in order of value.
+        let mut bytes = table_bytes();
+        let by_value = 21 + 8 * (VALUES.len() + 2);
+        bytes[by_value..by_value + 8].copy_from_slice(&0u64.to_le_bytes());
+        let file = TempFile::new("corrupt_index_err", &bytes);
+        match file.open::<u16>().unwrap().validate() {
+            Err(BinaryError::CorruptIndex) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_err() {
+        // Overwrite the first byte of "VBD", after "NNP" and two lengths.
+        let mut bytes = table_bytes();
+        bytes[VALUES_START + 8 + 3 + 8] = 0xff;
+        let file = TempFile::new("invalid_utf8_err", &bytes);
+        let t = file.open::<u16>().unwrap();
+        match t.validate() {
+            Err(BinaryError::InvalidValue { index: 1, .. }) => (),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(t.get_symbol(&0).unwrap().unwrap().data(), "NNP");
+        match t.get_symbol(&1) {
+            Err(BinaryError::InvalidValue { index: 1, .. }) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn unsorted_err() {
+        let mut t = Table::<Vec<u8>, u16>::new();
+        t.insert(b"NNP".to_vec());
+        let mut bytes = Vec::new();
+        t.write_to(&mut bytes).unwrap();
+        // Claim that there are no ids sorted by value.
+        bytes[37..45].copy_from_slice(&0u64.to_le_bytes());
+        let file = TempFile::new("unsorted_err", &bytes);
+        match file.open::<u16>() {
+            Err(BinaryError::CorruptIndex) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Serde support, enabled by the `serde` cargo feature.
+//!
+//! A `Table<T, D>` is serialized as a sequence of its values in id order. Ids
+//! are not written, since they must form a dense range starting at 0, and
+//! deserializing the sequence reinserts the values in the same order so that
+//! each receives its original id. A `HashIndexing` is serialized as a tuple of
+//! its underlying table, the number of its reserved symbols and the ids of its
+//! tombstones, and rebuilds its index when deserialized.
+
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeSeq, SerializeTuple, Serializer};
+
+use indexing::{HashIndexing, Indexing};
+use super::{SymbolId, Table};
+
+impl<T, D> Serialize for Table<T, D> where T: Serialize, D: SymbolId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        if !self.is_dense() {
+            return Err(ser::Error::custom("symbol ids do not form a dense range starting at 0"))
+        }
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for symbol in self.iter().rev() {
+            seq.serialize_element(symbol.data())?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, T, D> Deserialize<'de> for Table<T, D> where T: Deserialize<'de>, D: SymbolId {
+    fn deserialize<E>(deserializer: E) -> Result<Self, E::Error> where E: Deserializer<'de> {
+        deserializer.deserialize_seq(TableVisitor { marker: PhantomData, })
+    }
+}
+
+/// Rebuilds a `Table` from a sequence of values in id order.
+struct TableVisitor<T, D> where D: SymbolId {
+    marker: PhantomData<fn() -> Table<T, D>>,
+}
+
+impl<'de, T, D> Visitor<'de> for TableVisitor<T, D> where T: Deserialize<'de>, D: SymbolId {
+    type Value = Table<T, D>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of symbol table values")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error> where A: SeqAccess<'de> {
+        let mut table = Table::new();
+        while let Some(value) = seq.next_element()? {
+            table.try_insert(value).map_err(A::Error::custom)?;
+        }
+        Ok(table)
+    }
+}
+
+impl<T, D, H> Serialize for HashIndexing<T, D, H>
+    where T: Eq + Hash + Serialize, D: SymbolId, H: BuildHasher + Default {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let tombstones: Vec<usize> = self.tombstones.iter().map(|id| id.as_usize()).collect();
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(self.table())?;
+        tuple.serialize_element(&self.reserved)?;
+        tuple.serialize_element(&tombstones)?;
+        tuple.end()
+    }
+}
+
+impl<'de, T, D, H> Deserialize<'de> for HashIndexing<T, D, H>
+    where T: Eq + Hash + Deserialize<'de>, D: SymbolId, H: BuildHasher + Default {
+    fn deserialize<E>(deserializer: E) -> Result<Self, E::Error> where E: Deserializer<'de> {
+        deserializer.deserialize_tuple(3, HashIndexingVisitor { marker: PhantomData, })
+    }
+}
+
+/// Rebuilds a `HashIndexing` from its table, number of reserved symbols and
+/// tombstone ids.
+struct HashIndexingVisitor<T, D, H> where T: Eq + Hash, D: SymbolId {
+    marker: PhantomData<HashIndexing<T, D, H>>,
+}
+
+impl<'de, T, D, H> Visitor<'de> for HashIndexingVisitor<T, D, H>
+    where T: Eq + Hash + Deserialize<'de>, D: SymbolId, H: BuildHasher + Default {
+    type Value = HashIndexing<T, D, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a symbol table, a number of reserved symbols and tombstone ids")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error> where A: SeqAccess<'de> {
+        let table: Table<T, D> = seq.next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let reserved: usize = seq.next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let mut tombstones: Vec<usize> = seq.next_element()?
+            .ok_or_else(|| A::Error::invalid_length(2, &self))?;
+        if reserved > table.len() {
+            return Err(A::Error::custom("more reserved symbols than symbols in the table"))
+        }
+        tombstones.sort();
+        tombstones.dedup();
+        if tombstones.iter().any(|&id| id < reserved || id >= table.len()) {
+            return Err(A::Error::custom("tombstone id is reserved or not in the table"))
+        }
+        // The ids of a deserialized table are dense, so they are in id order.
+        let ids: Vec<D> = table.iter().rev().map(|symbol| *symbol.id()).collect();
+        let tombstones = tombstones.into_iter().map(|id| ids[id]).collect();
+        Ok(HashIndexing::from_parts(table, H::default(), reserved, tombstones))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indexing::{AlignPolicy, HashIndexing, Indexing};
+    use ::Table;
+
+    use serde_json;
+
+    const VALUES: &[&str] = &["NNP", "VBD", "DT", "NN", "IN"];
+
+    #[test]
+    fn table_round_trip_ok() {
+        let mut t = Table::<String, u16>::new();
+        for v in VALUES.iter() {
+            t.insert(v.to_string());
+        }
+        let json = serde_json::to_string(&t).unwrap();
+        assert_eq!(json, r#"["NNP","VBD","DT","NN","IN"]"#);
+
+        let t: Table<String, u16> = serde_json::from_str(&json).unwrap();
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            assert_eq!(t.get(&(i as u16)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn table_empty_round_trip_ok() {
+        let t = Table::<String, usize>::new();
+        let json = serde_json::to_string(&t).unwrap();
+        assert_eq!(json, "[]");
+        let t: Table<String, usize> = serde_json::from_str(&json).unwrap();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn table_sparse_ids_err() {
+        let mut t = Table::<String, u16>::new();
+        for v in VALUES.iter() {
+            t.insert(v.to_string());
+        }
+        t.remap(|symbol| if *symbol.id() == 0 { None } else { Some(*symbol.id()) });
+        assert!(serde_json::to_string(&t).is_err());
+    }
+
+    #[test]
+    fn table_too_many_values_err() {
+        let values: Vec<usize> = (0..257).collect();
+        let json = serde_json::to_string(&values).unwrap();
+        assert!(serde_json::from_str::<Table<usize, u8>>(&json).is_err());
+    }
+
+    #[test]
+    fn hash_indexing_reserved_round_trip_ok() {
+        let mut i = HashIndexing::<String, u8>::with_reserved(&["<pad>".to_string()]);
+        i.get_or_insert("NNP".to_string());
+        let json = serde_json::to_string(&i).unwrap();
+        assert_eq!(json, r#"[["<pad>","NNP"],1,[]]"#);
+        let i: HashIndexing<String, u8> = serde_json::from_str(&json).unwrap();
+        assert!(i.is_reserved(&0));
+        assert!(!i.is_reserved(&1));
+        assert_eq!(i.get_symbol_id("NNP"), Some(1));
+
+        assert!(serde_json::from_str::<HashIndexing<String, u8>>(r#"[["<pad>"],2,[]]"#).is_err());
+        assert!(serde_json::from_str::<HashIndexing<String, u8>>(r#"[["<pad>"],1]"#).is_err());
+        assert!(serde_json::from_str::<HashIndexing<String, u8>>(r#"[["<pad>"]]"#).is_err());
+    }
+
+    #[test]
+    fn hash_indexing_tombstones_round_trip_ok() {
+        let mut previous = HashIndexing::<String, u8>::default();
+        previous.encode_or_insert(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        let mut i = HashIndexing::<String, u8>::default();
+        i.encode_or_insert(vec!["c".to_string(), "a".to_string()]);
+        i.align_to(&previous, AlignPolicy::Tombstone("_".to_string()));
+        let json = serde_json::to_string(&i).unwrap();
+        assert_eq!(json, r#"[["a","_","c"],0,[1]]"#);
+        let i: HashIndexing<String, u8> = serde_json::from_str(&json).unwrap();
+        assert!(i.is_tombstone(&1));
+        assert!(i.get("_").is_none());
+        assert_eq!(i.get_symbol_id("c"), Some(2));
+
+        assert!(serde_json::from_str::<HashIndexing<String, u8>>(r#"[["a","_"],1,[0]]"#).is_err());
+        assert!(serde_json::from_str::<HashIndexing<String, u8>>(r#"[["a","_"],0,[2]]"#).is_err());
+    }
+
+    #[test]
+    fn hash_indexing_round_trip_ok() {
+        let mut i = HashIndexing::<String, usize>::default();
+        for v in VALUES.iter() {
+            i.get_or_insert(v.to_string());
+        }
+        let json = serde_json::to_string(&i).unwrap();
+        let i: HashIndexing<String, usize> = serde_json::from_str(&json).unwrap();
+        for (id, v) in VALUES.iter().enumerate() {
+            let symbol = i.get(&v.to_string()).unwrap();
+            assert_eq!(*symbol.id(), id);
+            assert_eq!(i.get_symbol(&id).unwrap().data(), v);
+        }
+    }
+}
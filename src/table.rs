@@ -1,10 +1,10 @@
 use std::cmp::{Eq, Ord, Ordering, PartialEq};
 use std::collections::HashMap;
 use std::default::Default;
+use std::error::Error;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::iter::Iterator;
-use std::mem;
+use std::iter::{Extend, FromIterator, Iterator};
 
 /// A table entry that associates an instance of `T` with an atomic symbol.
 ///
@@ -15,7 +15,6 @@ use std::mem;
 pub struct Symbol<T, D> where D: SymbolId {
     id: D,
     data: T,
-    next: Option<Box<Symbol<T, D>>>,
 }
 
 impl<T, D> Symbol<T, D> where D: SymbolId {
@@ -33,6 +32,11 @@ impl<T, D> Symbol<T, D> where D: SymbolId {
     pub fn data(&self) -> &T {
         &self.data
     }
+
+    /// Consumes the symbol, returning its data.
+    pub fn into_data(self) -> T {
+        self.data
+    }
 }
 
 impl<T, D> Hash for Symbol<T, D> where T: Hash, D: SymbolId {
@@ -65,67 +69,305 @@ impl<T, D> Ord for Symbol<T, D> where T: Ord, D: SymbolId {
 pub trait SymbolId:
 Copy + Clone + fmt::Debug + Default + Eq + Hash + Ord + PartialEq + PartialOrd + Send + Sync {
     /// Returns the ID immediately subsequent to this one.
-    fn next(&self) -> Self;
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is already the maximum value of the ID type.
+    fn next(&self) -> Self {
+        self.checked_next().expect("symbol id overflow")
+    }
+
+    /// Returns the ID immediately subsequent to this one, or `None` if `self`
+    /// is the maximum value of the ID type.
+    fn checked_next(&self) -> Option<Self>;
+
+    /// Returns the largest value of the ID type.
+    fn max_value() -> Self;
 
     /// Casts the ID to a `usize`.
     fn as_usize(&self) -> usize;
 }
 
 impl SymbolId for usize {
-    fn next(&self) -> Self { *self + 1 }
+    fn checked_next(&self) -> Option<Self> { self.checked_add(1) }
+    fn max_value() -> Self { usize::MAX }
     fn as_usize(&self) -> usize { *self }
 }
 
 impl SymbolId for u8 {
-    fn next(&self) -> Self { *self + 1 }
-
+    fn checked_next(&self) -> Option<Self> { self.checked_add(1) }
+    fn max_value() -> Self { u8::MAX }
     fn as_usize(&self) -> usize { *self as usize }
 }
 
 impl SymbolId for u16 {
-    fn next(&self) -> Self { *self + 1 }
+    fn checked_next(&self) -> Option<Self> { self.checked_add(1) }
+    fn max_value() -> Self { u16::MAX }
     fn as_usize(&self) -> usize { *self as usize }
 }
 
 impl SymbolId for u32 {
-    fn next(&self) -> Self { *self + 1 }
+    fn checked_next(&self) -> Option<Self> { self.checked_add(1) }
+    fn max_value() -> Self { u32::MAX }
     fn as_usize(&self) -> usize { *self as usize }
 }
 
 impl SymbolId for u64 {
-    fn next(&self) -> Self { *self + 1 }
+    fn checked_next(&self) -> Option<Self> { self.checked_add(1) }
+    fn max_value() -> Self { u64::MAX }
     fn as_usize(&self) -> usize { *self as usize }
 }
 
-/// The head of a linked list associating `T`s with `SymbolId`s. `SymbolId`
-/// values start at 0 and increase by 1 for each `T` added to the table.
+/// Error returned when a value cannot be inserted into a table because every
+/// value of its `SymbolId` type has already been assigned. The rejected value
+/// is handed back to the caller.
+#[derive(Clone, Eq, PartialEq)]
+pub struct CapacityError<T> {
+    value: T,
+}
+
+impl<T> CapacityError<T> {
+    pub(crate) fn new(value: T) -> Self {
+        CapacityError { value, }
+    }
+
+    /// Returns the value whose insertion was rejected.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CapacityError { .. }")
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("no symbol ids left to assign")
+    }
+}
+
+impl<T> Error for CapacityError<T> { }
+
+/// Summary of the symbols created by a bulk insertion such as
+/// `Table::insert_all()`.
+///
+/// New symbols are always assigned consecutive ids, so they are described by
+/// their number and the first and last ids assigned.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Extension<D> where D: SymbolId {
+    len: usize,
+    ids: Option<(D, D)>,
+}
+
+impl<D> Extension<D> where D: SymbolId {
+    pub(crate) fn new() -> Self {
+        Extension { len: 0, ids: None, }
+    }
+
+    /// Records that a new symbol was assigned `id`.
+    pub(crate) fn push(&mut self, id: D) {
+        self.len += 1;
+        self.ids = match self.ids {
+            Some((first, _)) => Some((first, id)),
+            None => Some((id, id)),
+        };
+    }
+
+    /// Returns the number of symbols created.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` iff no symbols were created.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the id of the first symbol created, if any.
+    pub fn first(&self) -> Option<&D> {
+        self.ids.as_ref().map(|(first, _)| first)
+    }
+
+    /// Returns the id of the last symbol created, if any.
+    pub fn last(&self) -> Option<&D> {
+        self.ids.as_ref().map(|(_, last)| last)
+    }
+
+    /// Returns `true` iff `id` was assigned to one of the symbols created.
+    pub fn contains(&self, id: &D) -> bool {
+        match self.ids {
+            Some((ref first, ref last)) => first <= id && id <= last,
+            None => false,
+        }
+    }
+}
+
+/// Error returned by `Table::try_remap()` when the requested ids do not form a
+/// dense range starting at 0, and by `HashIndexing::try_remap()` when they
+/// would also change the id of a reserved symbol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RemapError<D> where D: SymbolId {
+    len: usize,
+    duplicates: Vec<D>,
+    out_of_range: Vec<D>,
+    reserved: Vec<D>,
+}
+
+impl<D> RemapError<D> where D: SymbolId {
+    /// Creates an error for a remapping that would retain `len` symbols but
+    /// change or drop the ids in `reserved`, which must be sorted.
+    pub(crate) fn reserved_changed(len: usize, reserved: Vec<D>) -> Self {
+        RemapError { len, duplicates: Vec::new(), out_of_range: Vec::new(), reserved, }
+    }
+
+    /// Returns the number of symbols that would have been retained, which is
+    /// one past the largest id that the remapping was allowed to use.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true iff no symbols would have been retained.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the ids, in ascending order, that were assigned to more than one
+    /// symbol.
+    pub fn duplicates(&self) -> &[D] {
+        &self.duplicates
+    }
+
+    /// Returns the ids, in ascending order, that lie outside of the dense range
+    /// `0..len()`.
+    pub fn out_of_range(&self) -> &[D] {
+        &self.out_of_range
+    }
+
+    /// Returns the ids, in ascending order, of reserved symbols that would have
+    /// been given a different id or dropped.
+    pub fn reserved(&self) -> &[D] {
+        &self.reserved
+    }
+}
+
+impl<D> fmt::Display for RemapError<D> where D: SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.reserved.is_empty() {
+            return write!(f, "remapping would change reserved ids: {:?}", self.reserved)
+        }
+        write!(f, "remapped ids do not form a dense range 0..{}", self.len)?;
+        if !self.duplicates.is_empty() {
+            write!(f, "; duplicate ids: {:?}", self.duplicates)?;
+        }
+        if !self.out_of_range.is_empty() {
+            write!(f, "; out-of-range ids: {:?}", self.out_of_range)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D> Error for RemapError<D> where D: SymbolId { }
+
+/// Number of slots in each chunk of a `Table`.
+const CHUNK_LEN: usize = 1024;
+
+/// Marks an id that is not assigned to any symbol in `Table::by_id`.
+const VACANT: usize = usize::MAX;
+
+/// A chunked arena associating `T`s with `SymbolId`s. `SymbolId` values start
+/// at 0 and increase by 1 for each `T` added to the table.
 ///
-/// The linked list owns instances of `Symbol<T>`, which wrap around a `T` and a
-/// `SymbolId`. It satisfies the contract: *once allocated, a Symbol<T>'s
-/// address does not change as long as its parent table exists and it is not
-/// dropped from the table*.
+/// The table owns instances of `Symbol<T>`, which wrap around a `T` and a
+/// `SymbolId`. They are stored in the slots of fixed-size chunks, each of which
+/// is allocated once and never grown, and a symbol stays in its slot until it
+/// is dropped. The table therefore satisfies the contract: *once allocated, a
+/// Symbol<T>'s address does not change as long as its parent table exists and
+/// it is not dropped from the table*.
 ///
 /// As a result, a table index may retain a raw pointer to a `Symbol<T>` as long
 /// as care is taken not to dereference or otherwise make use of such pointers
-/// after the symbol they point to has been dropped by `retain()`.
+/// after the symbol they point to has been dropped by `remap()` or `retain()`.
+///
+/// Because chunks are never grown, capacity is managed in whole chunks:
+/// `with_capacity()` and `reserve()` allocate empty chunks up front, which are
+/// filled in order as symbols are inserted.
 #[derive(Debug)]
 pub struct Table<T, D> where D: SymbolId {
-    head: Option<Box<Symbol<T, D>>>,
-    next_id: D,
+    /// Slots in insertion order. A slot is emptied when its symbol is dropped.
+    chunks: Vec<Vec<Option<Symbol<T, D>>>>,
+    /// Empty chunks allocated ahead of time by `reserve()`.
+    spare: Vec<Vec<Option<Symbol<T, D>>>>,
+    /// The slot holding the symbol with each id, or `VACANT`. This is left
+    /// empty as long as the symbol with id `i` is in slot `i` for every slot,
+    /// which holds until a remapping moves an id or drops a symbol.
+    by_id: Vec<usize>,
+    len: usize,
+    next_id: Option<D>,
 }
 
 impl<T, D> Table<T, D> where D: SymbolId {
     /// Creates a new, empty table.
     pub fn new() -> Self {
         Table {
-            head: None,
-            next_id: Default::default(),
+            chunks: Vec::new(),
+            spare: Vec::new(),
+            by_id: Vec::new(),
+            len: 0,
+            next_id: Some(Default::default()),
+        }
+    }
+
+    /// Creates a new, empty table that can hold at least `capacity` symbols
+    /// without allocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut table = Table::new();
+        table.reserve(capacity);
+        table
+    }
+
+    /// Returns the number of symbols the table can hold without allocating.
+    /// This is always a multiple of the chunk size.
+    pub fn capacity(&self) -> usize {
+        (self.chunks.len() + self.spare.len()) * CHUNK_LEN
+    }
+
+    /// Reserves capacity for at least `additional` more symbols to be inserted
+    /// without allocating.
+    ///
+    /// Symbols already in the table are never moved by this operation.
+    pub fn reserve(&mut self, additional: usize) {
+        let wanted = self.slot_count().saturating_add(additional);
+        let capacity = self.capacity();
+        if wanted > capacity {
+            let new_chunks = (wanted - capacity).div_ceil(CHUNK_LEN);
+            self.chunks.reserve(self.spare.len() + new_chunks);
+            self.spare.reserve(new_chunks);
+            for _ in 0..new_chunks {
+                self.spare.push(Vec::with_capacity(CHUNK_LEN));
+            }
         }
     }
 
+    /// Releases chunks that were allocated by `reserve()` but hold no symbols.
+    ///
+    /// The partially filled last chunk, if any, is kept at full size, since
+    /// shrinking it would move the symbols it holds.
+    pub fn shrink_to_fit(&mut self) {
+        self.spare = Vec::new();
+        self.chunks.shrink_to_fit();
+    }
+
     /// Returns the number of symbols in the table.
     pub fn len(&self) -> usize {
-        self.next_id.as_usize()
+        self.len
+    }
+
+    /// Returns `true` iff the table contains no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     /// Inserts `value` into the table and assigns it an id. The same value may
@@ -133,22 +375,55 @@ impl<T, D> Table<T, D> where D: SymbolId {
     /// `get_or_insert()` method of `Indexing`.
     ///
     /// Returns a reference to the newly created symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every value of `D` has already been assigned. Use
+    /// `try_insert()` to handle this case.
     pub fn insert(&mut self, value: T) -> &Symbol<T, D> {
-        let next_id = self.next_id;
-        self.next_id = self.next_id.next();
-        let mut new_head = Box::new(Symbol {
-            id: next_id,
-            data: value,
-            next: None,
-        });
-        mem::swap(&mut self.head, &mut new_head.next);
-        self.head = Some(new_head);
-        (&self.head).as_ref().unwrap()
+        match self.try_insert(value) {
+            Ok(symbol) => symbol,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Inserts `value` into the table and assigns it an id, as `insert()`
+    /// does. If every value of `D` has already been assigned, returns an error
+    /// holding `value` instead.
+    pub fn try_insert(&mut self, value: T) -> Result<&Symbol<T, D>, CapacityError<T>> {
+        match self.next_id {
+            Some(id) => Ok(self.push(Symbol { id, data: value, })),
+            None => Err(CapacityError::new(value)),
+        }
+    }
+
+    /// Inserts every value produced by `values` into the table, as `insert()`
+    /// does, and returns a summary of the symbols created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table runs out of ids, as `insert()` does. Values inserted
+    /// before that point remain in the table.
+    pub fn insert_all<I>(&mut self, values: I) -> Extension<D> where I: IntoIterator<Item = T> {
+        let values = values.into_iter();
+        self.reserve(values.size_hint().0);
+        let mut extension = Extension::new();
+        for value in values {
+            extension.push(*self.insert(value).id());
+        }
+        extension
+    }
+
+    /// Looks up the symbol with id `id` in constant time. Returns
+    /// `Some(symbol)` if a symbol is present, else `None`.
+    pub fn get(&self, id: &D) -> Option<&Symbol<T, D>> {
+        self.lookup(id.as_usize()).filter(|symbol| symbol.id() == id)
     }
 
     /// Remaps associations between `T`s and `D`s, selectively dropping some
     /// associations entirely. The addresses of `Symbol<T>`s for entries which
-    /// are retained do not change.
+    /// are retained do not change. Symbols are visited in the same order as by
+    /// `iter()`.
     ///
     /// `(T, D)` associations for which `f` returns `Some(d)` will be remapped
     /// to use `d`.
@@ -160,73 +435,304 @@ impl<T, D> Table<T, D> where D: SymbolId {
     /// - The final mapping should be a dense range of whole numbers starting at 0.
     ///
     /// - No two different `T`s are associated with the same `D`.
-    pub fn remap<F>(&mut self, mut f: F) where F: FnMut(&Symbol<T, D>) -> Option<D> {
-        // Destructively walk linked list, selectively moving boxed symbols into
-        // a new list and reassigning `SymbolId`s as we go. This is done in
-        // place, without making new allocations for the elements that we
-        // retain.
-        let mut remapped = Table::new();
-        let mut head = None;
-        mem::swap(&mut head, &mut self.head);
-        loop {
-            head = match head {
-                None => break,
-                Some(mut symbol) =>
-                    if let Some(new_state_id) = f(&symbol) {
-                        let mut next_head = None;
-                        mem::swap(&mut next_head, &mut symbol.next);
-                        symbol.id = new_state_id;
-                        remapped.emplace_head(symbol);
-                        remapped.next_id = remapped.next_id.next();
-                        next_head
-                    } else {
-                        symbol.next
-                    },
+    ///
+    /// Use `try_remap()` to have these conditions checked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` assigns the same id to more than one symbol. The table is
+    /// left unchanged in this case.
+    pub fn remap<F>(&mut self, f: F) where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let assignments = self.assign(f);
+        self.apply_remap(assignments);
+    }
+
+    /// Remaps associations between `T`s and `D`s as `remap()` does, but first
+    /// verifies that the new ids form a dense range of whole numbers starting
+    /// at 0 with no duplicates. If they do not, returns an error describing the
+    /// offending ids and leaves the table unchanged.
+    pub fn try_remap<F>(&mut self, f: F) -> Result<(), RemapError<D>>
+        where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let assignments = self.assign(f);
+        let retained_len = assignments.iter().filter(|&&(_, id)| id.is_some()).count();
+        let mut seen = vec![false; retained_len];
+        let mut duplicates = Vec::new();
+        let mut out_of_range = Vec::new();
+        for id in assignments.iter().filter_map(|&(_, id)| id) {
+            match seen.get_mut(id.as_usize()) {
+                Some(true) => duplicates.push(id),
+                Some(seen) => *seen = true,
+                None => out_of_range.push(id),
             }
         }
-        mem::swap(&mut remapped, self);
+        if duplicates.is_empty() && out_of_range.is_empty() {
+            self.apply_remap(assignments);
+            return Ok(())
+        }
+        duplicates.sort();
+        duplicates.dedup();
+        out_of_range.sort();
+        out_of_range.dedup();
+        Err(RemapError { len: retained_len, duplicates, out_of_range, reserved: Vec::new(), })
     }
 
-    pub fn into_iter(self) -> TableIntoIter<T, D> {
-        TableIntoIter {
-            remaining: self.len(),
-            item: self.head,
+    /// Drops the symbols for which `f` returns `false` and renumbers the
+    /// remaining symbols so that their ids form a dense range starting at 0,
+    /// preserving their relative order. Symbols are visited in id order.
+    ///
+    /// Returns a vector that maps each old id (as a `usize` index) to the new id
+    /// of its symbol, or `None` if the symbol was dropped. As with `remap()`,
+    /// the retained symbols do not move.
+    pub fn retain<F>(&mut self, mut f: F) -> Vec<Option<D>>
+        where F: FnMut(&Symbol<T, D>) -> bool {
+        let slots = self.slots_by_id();
+        let mut translation = Vec::with_capacity(slots.len());
+        let mut assignments = Vec::with_capacity(self.len());
+        // At most len() ids are assigned, so this never runs out of ids before
+        // the last retained symbol is numbered.
+        let mut next_id = Some(D::default());
+        for slot in slots {
+            let new_id = match self.slot(slot) {
+                Some(symbol) if f(symbol) => {
+                    let id = next_id;
+                    next_id = next_id.and_then(|id| id.checked_next());
+                    id
+                },
+                _ => None,
+            };
+            if slot != VACANT {
+                assignments.push((slot, new_id));
+            }
+            translation.push(new_id);
         }
+        self.apply_remap(assignments);
+        translation
     }
 
-    /// Returns an iterator over table entries.
-    pub fn iter<'s>(&'s self) -> TableIter<'s, T, D> {
+    /// Returns an iterator over table entries, starting with the most recently
+    /// inserted symbol.
+    pub fn iter(&self) -> TableIter<'_, T, D> {
         TableIter {
+            table: self,
             remaining: self.len(),
-            item: (&self.head).as_ref(),
+            front: 0,
+            back: self.id_bound(),
+        }
+    }
+
+    /// Calls `f` on every symbol in the order of `iter()` and pairs the slot of
+    /// the symbol with the result.
+    fn assign<F>(&self, mut f: F) -> Vec<(usize, Option<D>)>
+        where F: FnMut(&Symbol<T, D>) -> Option<D> {
+        let mut assignments = Vec::with_capacity(self.len());
+        for slot in self.slots_by_id().into_iter().rev().filter(|&slot| slot != VACANT) {
+            assignments.push((slot, f(self.slot(slot).unwrap())));
         }
+        assignments
     }
 
-    /// Sets `value` as the head of this list. If `value` is already the head of
-    /// another list, its subsequent list elements are dropped.
-    fn emplace_head(&mut self, mut value: Box<Symbol<T, D>>) {
-        mem::swap(&mut value.next, &mut self.head);
-        mem::swap(&mut self.head, &mut Some(value));
+    /// Assigns each symbol, given by its slot, the id paired with it in
+    /// `assignments`, or drops it if that is `None`. Retained symbols stay in
+    /// their slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics before modifying the table if two symbols are assigned the same
+    /// id.
+    fn apply_remap(&mut self, assignments: Vec<(usize, Option<D>)>) {
+        let last_id = assignments.iter().filter_map(|&(_, id)| id).max();
+        let mut by_id = vec![VACANT; last_id.map_or(0, |id| id.as_usize() + 1)];
+        for &(slot, id) in assignments.iter() {
+            if let Some(id) = id {
+                let entry = &mut by_id[id.as_usize()];
+                if *entry != VACANT {
+                    panic!("symbol id {:?} assigned to more than one symbol", id);
+                }
+                *entry = slot;
+            }
+        }
+        for (slot, id) in assignments {
+            let entry = &mut self.chunks[slot / CHUNK_LEN][slot % CHUNK_LEN];
+            match id {
+                Some(id) => entry.as_mut().unwrap().id = id,
+                None => *entry = None,
+            }
+        }
+        self.len = by_id.iter().filter(|&&slot| slot != VACANT).count();
+        self.next_id = match last_id {
+            Some(id) => id.checked_next(),
+            None => Some(Default::default()),
+        };
+        // Empty slots at the end of the table can be released without moving
+        // any symbol.
+        while let Some(mut chunk) = self.chunks.pop() {
+            while let Some(None) = chunk.last() {
+                chunk.pop();
+            }
+            if !chunk.is_empty() {
+                self.chunks.push(chunk);
+                break;
+            }
+            self.spare.push(chunk);
+        }
+        let in_place = by_id.len() == self.slot_count()
+            && by_id.iter().enumerate().all(|(id, &slot)| id == slot);
+        self.by_id = if in_place { Vec::new() } else { by_id };
+    }
+
+    /// Returns the id that the next symbol inserted will be assigned, or `None`
+    /// if the id space is exhausted.
+    pub(crate) fn next_id(&self) -> Option<D> {
+        self.next_id
+    }
+
+    /// Returns `true` iff the ids in the table form a dense range starting at
+    /// 0, as they do unless a remapping left gaps.
+    pub(crate) fn is_dense(&self) -> bool {
+        self.id_bound() == self.len
+    }
+
+    /// Returns one past the largest id (as a `usize`) in the table.
+    pub(crate) fn id_bound(&self) -> usize {
+        if self.by_id.is_empty() { self.slot_count() } else { self.by_id.len() }
+    }
+
+    /// Returns the slot of the symbol with each id below `id_bound()`, or
+    /// `VACANT` for ids that are not assigned.
+    fn slots_by_id(&self) -> Vec<usize> {
+        if self.by_id.is_empty() {
+            (0..self.slot_count()).collect()
+        } else {
+            self.by_id.clone()
+        }
+    }
+
+    /// Returns the symbol whose id is `i` as a `usize`, if any.
+    fn lookup(&self, i: usize) -> Option<&Symbol<T, D>> {
+        if self.by_id.is_empty() {
+            self.slot(i)
+        } else {
+            self.by_id.get(i).and_then(|&slot| self.slot(slot))
+        }
+    }
+
+    /// Returns the symbol in slot `slot`, if it is occupied.
+    fn slot(&self, slot: usize) -> Option<&Symbol<T, D>> {
+        self.chunks.get(slot / CHUNK_LEN)
+            .and_then(|chunk| chunk.get(slot % CHUNK_LEN))
+            .and_then(Option::as_ref)
+    }
+
+    /// Returns the number of slots in use, including those emptied by a
+    /// remapping.
+    fn slot_count(&self) -> usize {
+        match self.chunks.last() {
+            Some(chunk) => (self.chunks.len() - 1) * CHUNK_LEN + chunk.len(),
+            None => 0,
+        }
+    }
+
+    /// Stores `symbol`, which must have the id returned by `next_id()`, in a
+    /// new slot after the last one, starting a new chunk (a spare one, if any
+    /// have been reserved) if the last one is full. Returns a reference to the
+    /// symbol in its final location.
+    fn push(&mut self, symbol: Symbol<T, D>) -> &Symbol<T, D> {
+        let full = match self.chunks.last() {
+            Some(chunk) => chunk.len() == CHUNK_LEN,
+            None => true,
+        };
+        if full {
+            // Chunks are never grown beyond their initial capacity, so the
+            // symbols they hold are never moved by a reallocation.
+            let chunk = self.spare.pop().unwrap_or_else(|| Vec::with_capacity(CHUNK_LEN));
+            self.chunks.push(chunk);
+        }
+        if !self.by_id.is_empty() {
+            let slot = self.slot_count();
+            self.by_id.push(slot);
+        }
+        self.len += 1;
+        self.next_id = symbol.id.checked_next();
+        let chunk = self.chunks.last_mut().unwrap();
+        chunk.push(Some(symbol));
+        chunk.last().unwrap().as_ref().unwrap()
+    }
+}
+
+impl<T, D> Default for Table<T, D> where D: SymbolId {
+    fn default() -> Self {
+        Table::new()
+    }
+}
+
+impl<T, D> FromIterator<T> for Table<T, D> where D: SymbolId {
+    fn from_iter<I>(values: I) -> Self where I: IntoIterator<Item = T> {
+        let mut table = Table::new();
+        table.insert_all(values);
+        table
+    }
+}
+
+impl<T, D> Extend<T> for Table<T, D> where D: SymbolId {
+    fn extend<I>(&mut self, values: I) where I: IntoIterator<Item = T> {
+        self.insert_all(values);
     }
 }
 
 impl<T, D> Table<T, D> where T: Eq + Hash, D: SymbolId {
+    /// Absorbs the symbols of `other` into the table. Values already present in
+    /// the table (or earlier in `other`) are dropped, and the others are
+    /// inserted in id order.
+    ///
+    /// Returns a vector that maps each id in `other` (as a `usize` index) to
+    /// the id of the same value in the merged table, or `None` if `other` did
+    /// not assign that id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every value of `D` is assigned before all new values have been
+    /// inserted. In this case, the table is left unchanged.
+    pub fn merge(&mut self, other: Table<T, D>) -> Vec<Option<D>> {
+        let mut translation = vec![None; other.id_bound()];
+        let mut symbols: Vec<Symbol<T, D>> = other.into_iter().collect();
+        symbols.sort_unstable_by_key(|symbol| symbol.id);
+        let (old_ids, values): (Vec<D>, Vec<T>) = symbols.into_iter()
+            .map(|symbol| (symbol.id, symbol.data))
+            .unzip();
+        let mut is_new = Vec::with_capacity(values.len());
+        {
+            let mut ids: HashMap<&T, D> = HashMap::with_capacity(self.len() + values.len());
+            // Duplicate values in the table map to their first occurrence.
+            for symbol in self.iter().rev() {
+                ids.entry(symbol.data()).or_insert(symbol.id);
+            }
+            let mut next_id = self.next_id();
+            for (old_id, value) in old_ids.into_iter().zip(values.iter()) {
+                let new = !ids.contains_key(value);
+                if new {
+                    let id = match next_id {
+                        Some(id) => id,
+                        None => panic!("no symbol ids left to assign"),
+                    };
+                    ids.insert(value, id);
+                    next_id = id.checked_next();
+                }
+                translation[old_id.as_usize()] = Some(ids[value]);
+                is_new.push(new);
+            }
+        }
+        let new_values = values.into_iter().zip(is_new).filter(|&(_, new)| new);
+        self.insert_all(new_values.map(|(value, _)| value));
+        translation
+    }
+
     /// Converts `self` to a `HashMap` holding the same associations as
     /// `self`. If the same key occurs in `self` more than once, then duplicate
     /// occurrences will be dropped arbitrarily.
-    pub fn to_hash_map(mut self) -> HashMap<T, D> {
+    pub fn to_hash_map(self) -> HashMap<T, D> {
         let mut map = HashMap::with_capacity(self.len());
-        loop {
-            self.head = match self.head {
-                None => break,
-                Some(mut symbol) => {
-                    let id = symbol.id().clone();
-                    let mut next_head = None;
-                    mem::swap(&mut next_head, &mut symbol.next);
-                    map.insert(symbol.data, id);
-                    next_head
-                },
-            }
+        for symbol in self {
+            map.insert(symbol.data, symbol.id);
         }
         map
     }
@@ -242,35 +748,44 @@ impl<'a, T, D> IntoIterator for &'a Table<T, D> where T: 'a, D: 'a + SymbolId {
 }
 
 impl<T, D> IntoIterator for Table<T, D> where D: SymbolId {
-    type Item = Box<Symbol<T, D>>;
+    type Item = Symbol<T, D>;
     type IntoIter = TableIntoIter<T, D>;
 
+    /// Returns an iterator that consumes the table, starting with the most
+    /// recently inserted symbol.
     fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        TableIntoIter {
+            remaining: self.len(),
+            front: 0,
+            back: self.id_bound(),
+            chunks: self.chunks,
+            by_id: self.by_id,
+        }
     }
 }
 
 /// Iterator over table contents.
 #[derive(Debug)]
 pub struct TableIter<'a, T, D> where T: 'a, D: 'a + SymbolId {
+    table: &'a Table<T, D>,
     remaining: usize,
-    item: Option<&'a Box<Symbol<T, D>>>,
+    /// Ids (as `usize` values) that have not been visited from either end.
+    front: usize,
+    back: usize,
 }
 
 impl<'a, T, D> Iterator for TableIter<'a, T, D> where T: 'a, D: 'a + SymbolId {
     type Item = &'a Symbol<T, D>;
 
     fn next(&mut self) -> Option<&'a Symbol<T, D>> {
-        let mut item = None;
-        mem::swap(&mut item, &mut self.item);
-        match item {
-            None => None,
-            Some(symbol) => {
+        while self.front < self.back {
+            self.back -= 1;
+            if let Some(symbol) = self.table.lookup(self.back) {
                 self.remaining -= 1;
-                self.item = symbol.next.as_ref();
-                Some(symbol)
-            },
+                return Some(symbol)
+            }
         }
+        None
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -278,27 +793,57 @@ impl<'a, T, D> Iterator for TableIter<'a, T, D> where T: 'a, D: 'a + SymbolId {
     }
 }
 
+impl<'a, T, D> DoubleEndedIterator for TableIter<'a, T, D> where T: 'a, D: 'a + SymbolId {
+    fn next_back(&mut self) -> Option<&'a Symbol<T, D>> {
+        while self.front < self.back {
+            self.front += 1;
+            if let Some(symbol) = self.table.lookup(self.front - 1) {
+                self.remaining -= 1;
+                return Some(symbol)
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T, D> ExactSizeIterator for TableIter<'a, T, D> where T: 'a, D: 'a + SymbolId { }
+
 /// Iterator that consumes a table.
 #[derive(Debug)]
 pub struct TableIntoIter<T, D> where D: SymbolId {
     remaining: usize,
-    item: Option<Box<Symbol<T, D>>>,
+    front: usize,
+    back: usize,
+    chunks: Vec<Vec<Option<Symbol<T, D>>>>,
+    by_id: Vec<usize>,
+}
+
+impl<T, D> TableIntoIter<T, D> where D: SymbolId {
+    /// Moves out the symbol whose id is `i` as a `usize`, if any.
+    fn take(&mut self, i: usize) -> Option<Symbol<T, D>> {
+        let slot = if self.by_id.is_empty() { i } else { self.by_id[i] };
+        let symbol = self.chunks.get_mut(slot / CHUNK_LEN)
+            .and_then(|chunk| chunk.get_mut(slot % CHUNK_LEN))
+            .and_then(Option::take);
+        if symbol.is_some() {
+            self.remaining -= 1;
+        }
+        symbol
+    }
 }
 
 impl<T, D> Iterator for TableIntoIter<T, D> where D: SymbolId {
-    type Item = Box<Symbol<T, D>>;
-
-    fn next(&mut self) -> Option<Box<Symbol<T, D>>> {
-        let mut item = None;
-        mem::swap(&mut item, &mut self.item);
-        match item {
-            None => None,
-            Some(mut symbol) => {
-                self.remaining -= 1;
-                mem::swap(&mut self.item, &mut symbol.next);
-                Some(symbol)
-            },
+    type Item = Symbol<T, D>;
+
+    fn next(&mut self) -> Option<Symbol<T, D>> {
+        while self.front < self.back {
+            self.back -= 1;
+            let i = self.back;
+            if let Some(symbol) = self.take(i) {
+                return Some(symbol)
+            }
         }
+        None
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -306,9 +851,25 @@ impl<T, D> Iterator for TableIntoIter<T, D> where D: SymbolId {
     }
 }
 
+impl<T, D> DoubleEndedIterator for TableIntoIter<T, D> where D: SymbolId {
+    fn next_back(&mut self) -> Option<Symbol<T, D>> {
+        while self.front < self.back {
+            self.front += 1;
+            let i = self.front - 1;
+            if let Some(symbol) = self.take(i) {
+                return Some(symbol)
+            }
+        }
+        None
+    }
+}
+
+impl<T, D> ExactSizeIterator for TableIntoIter<T, D> where D: SymbolId { }
+
 #[cfg(test)]
+#[allow(clippy::clone_on_copy, clippy::redundant_static_lifetimes)]
 mod test {
-    use super::{Symbol, SymbolId, Table};
+    use super::{CHUNK_LEN, Symbol, SymbolId, Table};
 
     use std::collections::HashMap;
     use std::default::Default;
@@ -324,12 +885,177 @@ mod test {
         assert_eq!(id.as_usize(), 0);
     }
 
+    #[test]
+    fn symbol_id_checked_next_ok() {
+        assert_eq!(0u8.checked_next(), Some(1));
+        assert_eq!(254u8.checked_next(), Some(255));
+        assert_eq!(255u8.checked_next(), None);
+        assert_eq!(<u8 as SymbolId>::max_value(), 255);
+        assert_eq!(<u16 as SymbolId>::max_value().checked_next(), None);
+        assert_eq!(<u32 as SymbolId>::max_value().checked_next(), None);
+        assert_eq!(<u64 as SymbolId>::max_value().checked_next(), None);
+        assert_eq!(<usize as SymbolId>::max_value().checked_next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn symbol_id_next_overflow_panics() {
+        255u8.next();
+    }
+
+    #[test]
+    fn table_try_insert_exhausted_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in 0..256 {
+            assert_eq!(t.try_insert(v).unwrap().id().as_usize(), v);
+        }
+        assert_eq!(t.len(), 256);
+        assert_eq!(t.next_id(), None);
+        assert_eq!(t.try_insert(256).unwrap_err().into_value(), 256);
+        assert_eq!(t.len(), 256);
+        assert_eq!(*t.get(&255).unwrap().data(), 255);
+    }
+
+    #[test]
+    #[should_panic]
+    fn table_insert_exhausted_panics() {
+        let mut t = Table::<usize, u8>::new();
+        for v in 0..257 {
+            t.insert(v);
+        }
+    }
+
     #[test]
     fn new_table_empty_ok() {
         let t = Table::<usize, usize>::new();
-        assert!(t.head.is_none());
-        assert!(t.next_id == 0);
+        assert!(t.chunks.is_empty());
+        assert_eq!(t.next_id(), Some(0));
         assert_eq!(t.len(), 0);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn table_with_capacity_ok() {
+        let mut t = Table::<usize, usize>::with_capacity(CHUNK_LEN + 1);
+        assert!(t.is_empty());
+        assert_eq!(t.capacity(), 2 * CHUNK_LEN);
+        for v in 0..(CHUNK_LEN + 1) {
+            t.insert(v);
+        }
+        assert_eq!(t.capacity(), 2 * CHUNK_LEN);
+        assert_eq!(t.chunks.len(), 2);
+        assert!(t.spare.is_empty());
+        assert_eq!(*t.get(&CHUNK_LEN).unwrap().data(), CHUNK_LEN);
+    }
+
+    #[test]
+    fn table_reserve_ok() {
+        let mut t = Table::<usize, usize>::new();
+        assert_eq!(t.capacity(), 0);
+        t.reserve(0);
+        assert_eq!(t.capacity(), 0);
+        t.insert(0);
+        let address = t.get(&0).unwrap() as *const Symbol<usize, usize>;
+        assert_eq!(t.capacity(), CHUNK_LEN);
+        t.reserve(CHUNK_LEN - 1);
+        assert_eq!(t.capacity(), CHUNK_LEN);
+        t.reserve(CHUNK_LEN);
+        assert_eq!(t.capacity(), 2 * CHUNK_LEN);
+        t.reserve(3 * CHUNK_LEN);
+        assert_eq!(t.capacity(), 4 * CHUNK_LEN);
+        assert_eq!(t.get(&0).unwrap() as *const Symbol<usize, usize>, address);
+        assert_eq!(t.len(), 1);
+    }
+
+    #[test]
+    fn table_shrink_to_fit_ok() {
+        let mut t = Table::<usize, usize>::with_capacity(3 * CHUNK_LEN);
+        t.insert(0);
+        let address = t.get(&0).unwrap() as *const Symbol<usize, usize>;
+        t.shrink_to_fit();
+        assert_eq!(t.capacity(), CHUNK_LEN);
+        assert_eq!(t.get(&0).unwrap() as *const Symbol<usize, usize>, address);
+        t.insert(1);
+        assert_eq!(t.len(), 2);
+
+        let mut t = Table::<usize, usize>::with_capacity(10);
+        t.shrink_to_fit();
+        assert_eq!(t.capacity(), 0);
+    }
+
+    #[test]
+    fn table_insert_all_ok() {
+        let mut t = Table::<usize, usize>::new();
+        let e = t.insert_all(VALUES.iter().cloned());
+        assert_eq!(e.len(), VALUES.len());
+        assert_eq!(e.first(), Some(&0));
+        assert_eq!(e.last(), Some(&(VALUES.len() - 1)));
+        let e = t.insert_all(vec![7, 7]);
+        assert_eq!(e.len(), 2);
+        assert_eq!(e.first(), Some(&VALUES.len()));
+        assert_eq!(e.last(), Some(&(VALUES.len() + 1)));
+        assert!(e.contains(&VALUES.len()));
+        assert!(!e.contains(&0));
+        let e = t.insert_all(Vec::new());
+        assert!(e.is_empty());
+        assert_eq!(e.first(), None);
+        assert!(!e.contains(&0));
+        assert_eq!(t.len(), VALUES.len() + 2);
+    }
+
+    #[test]
+    fn table_from_iter_ok() {
+        let mut t: Table<usize, usize> = VALUES.iter().cloned().collect();
+        assert_eq!(t.len(), VALUES.len());
+        t.extend(VALUES.iter().cloned());
+        assert_eq!(t.len(), 2 * VALUES.len());
+        for (i, v) in VALUES.iter().chain(VALUES.iter()).enumerate() {
+            assert_eq!(t.get(&i).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn table_merge_ok() {
+        let mut t = Table::<&str, u8>::new();
+        t.insert_all(vec!["a", "b", "c", "b"]);
+        let mut other = Table::<&str, u8>::new();
+        other.insert_all(vec!["d", "b", "e", "d", "a"]);
+        assert_eq!(t.merge(other), vec![Some(4), Some(1), Some(5), Some(4), Some(0)]);
+        assert_eq!(t.len(), 6);
+        assert_eq!(t.get(&4).unwrap().data(), &"d");
+        assert_eq!(t.get(&5).unwrap().data(), &"e");
+
+        assert_eq!(t.merge(Table::new()), Vec::<Option<u8>>::new());
+        let mut empty = Table::<&str, u8>::new();
+        assert_eq!(empty.merge(t), vec![Some(0), Some(1), Some(2), Some(1), Some(3), Some(4)]);
+        assert_eq!(empty.len(), 5);
+    }
+
+    #[test]
+    fn table_merge_sparse_ids_ok() {
+        let mut t = Table::<&str, u8>::new();
+        t.insert_all(vec!["a", "b"]);
+        let mut other = Table::<&str, u8>::new();
+        other.insert_all(vec!["x", "a", "y"]);
+        other.remap(|symbol| Some([4, 0, 2][*symbol.id() as usize]));
+        // New values are inserted in the order of their ids in `other`.
+        assert_eq!(t.merge(other), vec![Some(0), None, Some(2), None, Some(3)]);
+        assert_eq!(t.len(), 4);
+        assert_eq!(t.get(&2).unwrap().data(), &"y");
+        assert_eq!(t.get(&3).unwrap().data(), &"x");
+    }
+
+    #[test]
+    fn table_merge_exhausted_panics_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut t = Table::<usize, u8>::new();
+        t.insert_all(0..255);
+        let mut other = Table::<usize, u8>::new();
+        other.insert_all(vec![0, 1000, 1001]);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| t.merge(other)));
+        assert!(result.is_err());
+        assert_eq!(t.len(), 255);
     }
 
     #[test]
@@ -338,28 +1064,106 @@ mod test {
         for (i, v) in VALUES.iter().enumerate() {
             t.insert(*v);
             assert_eq!(t.len(), i + 1);
-            assert_eq!(t.next_id.as_usize(), i + 1);
-            assert_eq!(t.head.as_ref().map(|x| x.data), Some(*v));
+            assert_eq!(t.next_id(), Some(i + 1));
+            assert_eq!(t.chunks.last().and_then(|c| c.last()).map(|x| x.as_ref().unwrap().data),
+                       Some(*v));
         }
         assert_eq!(t.len(), VALUES.len());
-        assert_eq!(t.next_id.as_usize(), VALUES.len());
+        assert_eq!(t.next_id(), Some(VALUES.len()));
+        assert_eq!(t.chunks.len(), 1);
 
-        let mut x = t.head.as_ref();
         let mut count = 0;
-        let mut vs = VALUES.iter().rev().enumerate();
-        loop {
-            x = match x {
-                None => break,
-                Some(symbol) => {
-                    let (i, v) = vs.next().unwrap();
-                    assert_eq!(i, count);
-                    assert_eq!(symbol.data(), v);
-                    count += 1;
-                    symbol.next.as_ref()
-                },
-            }
+        for (i, (symbol, v)) in t.chunks[0].iter().zip(VALUES.iter()).enumerate() {
+            let symbol = symbol.as_ref().unwrap();
+            assert_eq!(symbol.id().as_usize(), i);
+            assert_eq!(symbol.data(), v);
+            count += 1;
+        }
+        assert_eq!(count, VALUES.len());
+    }
+
+    #[test]
+    fn table_insert_across_chunks_ok() {
+        let mut t = Table::<usize, usize>::new();
+        let n = 2 * CHUNK_LEN + 1;
+        let mut symbol_addresses = Vec::with_capacity(n);
+        for v in 0..n {
+            let symbol = t.insert(v);
+            assert_eq!(symbol.id().as_usize(), v);
+            symbol_addresses.push(symbol as *const Symbol<usize, usize>);
         }
-        assert_eq!(vs.next(), None);
+        assert_eq!(t.len(), n);
+        assert_eq!(t.chunks.len(), 3);
+        for chunk in t.chunks.iter() {
+            assert_eq!(chunk.capacity(), CHUNK_LEN);
+        }
+
+        let mut count = 0;
+        for (symbol, (v, address)) in t.iter().zip(
+            (0..n).rev().zip(symbol_addresses.into_iter().rev())) {
+            assert_eq!(*symbol.data(), v);
+            assert_eq!(*symbol.id(), v);
+            assert_eq!(symbol as *const Symbol<usize, usize>, address);
+            count += 1;
+        }
+        assert_eq!(count, n);
+    }
+
+    #[test]
+    fn table_get_ok() {
+        let mut t = Table::<usize, u32>::new();
+        assert!(t.get(&0).is_none());
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        for (i, v) in VALUES.iter().enumerate() {
+            let symbol = t.get(&(i as u32)).unwrap();
+            assert_eq!(symbol.id().as_usize(), i);
+            assert_eq!(symbol.data(), v);
+        }
+        assert!(t.get(&(VALUES.len() as u32)).is_none());
+    }
+
+    #[test]
+    fn table_get_across_chunks_ok() {
+        let mut t = Table::<usize, usize>::new();
+        let n = 2 * CHUNK_LEN + 1;
+        for v in 0..n {
+            t.insert(v * 2);
+        }
+        for i in 0..n {
+            assert_eq!(*t.get(&i).unwrap().data(), i * 2);
+        }
+        assert!(t.get(&n).is_none());
+    }
+
+    #[test]
+    fn table_get_after_remap_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        // Reverse the id assignment and drop the oldest symbol.
+        let last = VALUES.len() as u8 - 1;
+        t.remap(|symbol| if *symbol.id() == 0 { None } else { Some(last - *symbol.id()) });
+        assert_eq!(t.len(), VALUES.len() - 1);
+        for (i, v) in VALUES.iter().enumerate().skip(1) {
+            let symbol = t.get(&(last - i as u8)).unwrap();
+            assert_eq!(symbol.data(), v);
+        }
+        assert!(t.get(&last).is_none());
+    }
+
+    #[test]
+    fn table_iter_rev_ok() {
+        let mut t = Table::<usize, u32>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let forward: Vec<usize> = t.iter().rev().map(|s| *s.data()).collect();
+        assert_eq!(forward, VALUES);
+        let ids: Vec<u32> = t.into_iter().rev().map(|s| *s.id()).collect();
+        assert_eq!(ids, (0..VALUES.len() as u32).collect::<Vec<u32>>());
     }
 
     #[test]
@@ -489,6 +1293,164 @@ mod test {
         assert_eq!(t.len(), 0);
     }
 
+    #[test]
+    fn remap_retained_address_unchanged_ok() {
+        let mut t = Table::<usize, u8>::new();
+        let mut addresses = Vec::new();
+        for v in VALUES.iter() {
+            addresses.push(t.insert(*v) as *const Symbol<usize, u8>);
+        }
+        // Drop the odd ids and reverse the order of the others.
+        t.remap(|symbol| if symbol.id() % 2 == 0 { Some(2 - symbol.id() / 2) } else { None });
+        assert_eq!(t.len(), 3);
+        for (old_id, address) in addresses.into_iter().enumerate().step_by(2) {
+            let symbol = t.get(&(2 - old_id as u8 / 2)).unwrap();
+            assert_eq!(*symbol.data(), VALUES[old_id]);
+            assert_eq!(symbol as *const Symbol<usize, u8>, address);
+        }
+        assert_eq!(t.iter().rev().map(|s| *s.data()).collect::<Vec<usize>>(), vec![0, 500, 101]);
+        assert_eq!(t.insert(7).id(), &3);
+        assert_eq!(t.into_iter().map(|s| *s.data()).collect::<Vec<usize>>(),
+                   vec![7, 101, 500, 0]);
+    }
+
+    #[test]
+    fn remap_sparse_ok() {
+        let mut t = Table::<usize, u8>::new();
+        t.insert_all(VALUES.iter().cloned());
+        t.remap(|symbol| if *symbol.id() < 3 { Some(*symbol.id() * 2) } else { None });
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.iter().map(|s| *s.id()).collect::<Vec<u8>>(), vec![4, 2, 0]);
+        assert!(t.get(&1).is_none());
+        assert_eq!(*t.get(&4).unwrap().data(), 500);
+        assert_eq!(t.insert(7).id(), &5);
+    }
+
+    #[test]
+    fn remap_duplicates_panics_unchanged() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut t = Table::<usize, u8>::new();
+        t.insert_all(VALUES.iter().cloned());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| t.remap(|_| Some(0))));
+        assert!(result.is_err());
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            assert_eq!(t.get(&(i as u8)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn try_remap_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let mut new_id = 0u8;
+        let mut expected_associations = HashMap::new();
+        let result = t.try_remap(|symbol|
+                                 if symbol.id() % 2 == 0 {
+                                     let id = new_id;
+                                     new_id += 1;
+                                     expected_associations.insert(*symbol.data(), id);
+                                     Some(id)
+                                 } else {
+                                     None
+                                 });
+        assert_eq!(result, Ok(()));
+        assert_eq!(t.to_hash_map(), expected_associations);
+    }
+
+    #[test]
+    fn try_remap_duplicates_err() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let err = t.try_remap(|symbol| Some(*symbol.id() / 2)).unwrap_err();
+        assert_eq!(err.len(), VALUES.len());
+        assert_eq!(err.duplicates(), &[0, 1, 2]);
+        assert!(err.out_of_range().is_empty());
+        assert_eq!(err.to_string(),
+                   "remapped ids do not form a dense range 0..6; duplicate ids: [0, 1, 2]");
+
+        // The table is left unchanged.
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            assert_eq!(t.get(&(i as u8)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn try_remap_not_dense_err() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let err = t.try_remap(|symbol|
+                              if *symbol.id() == 0 { None } else { Some(*symbol.id() * 2) })
+            .unwrap_err();
+        assert_eq!(err.len(), VALUES.len() - 1);
+        assert!(err.duplicates().is_empty());
+        assert_eq!(err.out_of_range(), &[6, 8, 10]);
+        assert_eq!(err.to_string(),
+                   "remapped ids do not form a dense range 0..5; out-of-range ids: [6, 8, 10]");
+
+        assert_eq!(t.len(), VALUES.len());
+        for (i, v) in VALUES.iter().enumerate() {
+            assert_eq!(t.get(&(i as u8)).unwrap().data(), v);
+        }
+    }
+
+    #[test]
+    fn retain_some_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let mut visited = Vec::new();
+        let translation = t.retain(|symbol| {
+            visited.push(*symbol.data());
+            symbol.data() % 2 == 1
+        });
+        assert_eq!(visited, VALUES);
+        assert_eq!(translation, vec![Some(0), Some(1), None, None, None, Some(2)]);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.iter().rev().map(|s| *s.data()).collect::<Vec<usize>>(),
+                   vec![101, 203, 1]);
+        for (old_id, v) in VALUES.iter().enumerate() {
+            if let Some(new_id) = translation[old_id] {
+                assert_eq!(t.get(&new_id).unwrap().data(), v);
+            }
+        }
+    }
+
+    #[test]
+    fn retain_all_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in 0..256 {
+            t.insert(v);
+        }
+        let translation = t.retain(|_| true);
+        assert_eq!(t.len(), 256);
+        for (old_id, new_id) in translation.into_iter().enumerate() {
+            assert_eq!(new_id.map(|id| id.as_usize()), Some(old_id));
+            assert_eq!(*t.get(&(old_id as u8)).unwrap().data(), old_id);
+        }
+    }
+
+    #[test]
+    fn retain_none_ok() {
+        let mut t = Table::<usize, u8>::new();
+        for v in VALUES.iter() {
+            t.insert(*v);
+        }
+        let translation = t.retain(|_| false);
+        assert_eq!(translation, vec![None; VALUES.len()]);
+        assert!(t.is_empty());
+        assert_eq!(t.insert(7).id(), &0);
+    }
+
     #[test]
     fn table_empty_into_iter_ok() {
         let t = Table::<usize, u8>::new();